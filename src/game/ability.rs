@@ -0,0 +1,67 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::Hitbox;
+use collider::geom::{Shape, Vec2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::Idx2;
+use super::util::idx_to_vec;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Ability { DoubleJump, AirDash }
+
+// abilities held by the player, kept through warps but lost on death
+#[derive(Copy, Clone)]
+pub struct Abilities { double_jump: bool, air_dash: bool }
+
+impl Abilities {
+    pub fn none() -> Abilities { Abilities { double_jump: false, air_dash: false } }
+
+    pub fn grant(&mut self, ability: Ability) {
+        match ability {
+            Ability::DoubleJump => self.double_jump = true,
+            Ability::AirDash => self.air_dash = true,
+        }
+    }
+
+    pub fn has(&self, ability: Ability) -> bool {
+        match ability {
+            Ability::DoubleJump => self.double_jump,
+            Ability::AirDash => self.air_dash,
+        }
+    }
+}
+
+pub struct Pickup { ability: Ability, pos: Vec2 }
+
+impl Pickup {
+    pub fn new(ability: Ability, pos: Idx2) -> Pickup { Pickup { ability, pos: idx_to_vec(pos) } }
+
+    pub fn ability(&self) -> Ability { self.ability }
+
+    pub fn hitbox(&self) -> Hitbox { Shape::circle(9.).place(self.pos).still() }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
+        let tex = match self.ability {
+            Ability::DoubleJump => SpriteId::DoubleJumpPickup,
+            Ability::AirDash => SpriteId::AirDashPickup,
+        };
+        renderer.draw(&affine.pre_translate(0., (4. * time).sin()), tex);
+    }
+}
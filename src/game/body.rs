@@ -0,0 +1,80 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use collider::{Hitbox, HbProfile};
+use collider::geom::{PlacedShape, Vec2, Card, CardMask, v2};
+
+use super::piece_profile::PieceProfile;
+use super::gravity::Gravity;
+use super::surface::Surface;
+use super::util::nearest_card;
+
+// a simple physics body that falls under gravity and is stopped by the barriers it touches,
+// used for pieces other than the player, such as crates; unlike the player, it is kept in world coordinates
+pub struct Body { vel: Vec2, gravity: Gravity, blocked_cards: CardMask, barrier_vels: [Vec2; 4] }
+
+impl Body {
+    pub fn new() -> Body {
+        Body { vel: Vec2::zero(), gravity: Gravity::normal(), blocked_cards: CardMask::empty(), barrier_vels: [Vec2::zero(); 4] }
+    }
+
+    pub fn vel(&self) -> Vec2 { self.vel }
+    pub fn gravity(&self) -> Gravity { self.gravity }
+
+    fn down(&self) -> Card { self.gravity.orient_card(Card::MinusY) }
+
+    pub fn is_on_ground(&self) -> bool { self.blocked_cards[self.down()] }
+
+    // each barrier is given along with the sides of it that can be touched, and the surface that carries
+    // the body along when it rests on top, such as a conveyor
+    pub fn update_barriers<I: Iterator<Item=(Hitbox, CardMask, Surface)>>(&mut self, shape: PlacedShape, barriers: I) {
+        self.blocked_cards = CardMask::empty();
+        self.barrier_vels = [Vec2::zero(); 4];
+        for (hitbox, mask, surface) in barriers {
+            let normal = shape.masked_normal_from(&hitbox.value, mask);
+            if normal.len() < PieceProfile::padding() {
+                let dir = nearest_card(normal.dir()).flip();
+                self.blocked_cards[dir] = true;
+                let surface_vel = if dir == self.down() { surface.vel } else { Vec2::zero() };
+                self.barrier_vels[dir as usize] = hitbox.vel.value + surface_vel;
+            }
+        }
+        self.bound_vel();
+    }
+
+    // bodies on the ground move along with it unless pushed, and keep their horizontal speed in the air
+    pub fn step(&mut self, elapsed: f64, push_speed: Option<f64>, gravity: Gravity, fall_accel: f64, max_fall_speed: f64) {
+        self.gravity = gravity;
+        let floor_vel = self.barrier_vel(self.down());
+        if let Some(push_speed) = push_speed {
+            self.vel.x = push_speed + floor_vel.x;
+        } else if self.is_on_ground() {
+            self.vel.x = floor_vel.x;
+        }
+        let fall_vel = (gravity.orient(self.vel).y - fall_accel * gravity.scale * elapsed).max(-max_fall_speed);
+        self.vel = gravity.orient(v2(self.vel.x, fall_vel));
+        self.bound_vel();
+    }
+
+    fn barrier_vel(&self, card: Card) -> Vec2 { self.barrier_vels[card as usize] }
+
+    fn bound_vel(&mut self) {
+        if self.blocked_cards[Card::MinusX] { self.vel.x = self.vel.x.max(self.barrier_vel(Card::MinusX).x); }
+        if self.blocked_cards[Card::PlusX] { self.vel.x = self.vel.x.min(self.barrier_vel(Card::PlusX).x); }
+        if self.blocked_cards[Card::MinusY] { self.vel.y = self.vel.y.max(self.barrier_vel(Card::MinusY).y); }
+        if self.blocked_cards[Card::PlusY] { self.vel.y = self.vel.y.min(self.barrier_vel(Card::PlusY).y); }
+    }
+}
@@ -20,31 +20,62 @@ use std::mem;
 use collider::{Collider, HbId};
 use collider::geom::{Shape, v2, Vec2, Card};
 
+use crate::game_input::HorizDir;
 use super::{GameBoard, Idx2, PlatformKind, CELL_LEN};
 use super::player_enum::PlayerEnum;
 use super::star::Star;
 use super::piece_profile::{PieceProfile, PieceKind};
 use super::step_queue::StepQueue;
-use super::cell::Cell;
-use super::button::{self, ButtonAction};
-use super::warp::{WarpColor, LasorKind, Lasor};
+use super::cell::{self, Cell};
+use super::button::{self, Button, ButtonKind};
+use super::logic::{Logic, Group, Target};
+use super::warp::{WarpColor, LasorKind, LasorSchedule, Lasor, Warp};
 use super::util::{IdGen, idx_to_vec, card_offset};
+use super::ability::{Ability, Pickup};
+use super::key::{Key, KeyColor};
+use super::spring::Spring;
+use super::portal::Portal;
+use super::gravity::GravityZone;
+use super::water::WaterCell;
+use super::enemy::{Enemy, EnemyKind};
+use super::crates::Crate;
+use super::plate::{self, Plate};
+use super::physics::PhysicsProfile;
+use super::platform::PlatformStyle;
+use super::surface::Material;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
-enum PendingCell { Wall, Floor, Gate, Spawn(WarpColor, bool) }
+enum PendingCell {
+    Wall, Floor, Gate, ChirpBlock, Door(KeyColor), Spawn(WarpColor, bool, bool), Conveyor(HorizDir), Material(Material),
+}
 
 pub struct GameBoardBuilder {
     id_gen: IdGen,
     collider: Collider<PieceProfile>,
     room_dims: Idx2,
-    player: Option<PlayerEnum>,
+    start_pos: Option<Vec2>,
     star: Option<Star>,
-    platforms: Vec<(Idx2, PlatformKind)>,
+    platforms: Vec<(Idx2, PlatformKind, PlatformStyle)>,
+    dormant_platforms: Vec<Idx2>,
     grid: HashMap<Idx2, PendingCell>,
-    buttons: HashMap<u32, (Option<Idx2>, ButtonAction)>,
+    buttons: HashMap<u32, (Idx2, ButtonKind)>,
+    groups: HashMap<u32, Group>,
+    chirp_blocks: Vec<Idx2>,
+    logic: Logic,
     warps: Vec<(Idx2, WarpColor)>,
     respawns: HashMap<WarpColor, Vec2>,
-    lasors: Vec<(Idx2, LasorKind, WarpColor)>,
+    respawn_stations: Vec<(Idx2, WarpColor)>,
+    lasors: Vec<(Idx2, LasorKind, WarpColor, LasorSchedule)>,
+    pickups: Vec<Pickup>,
+    keys: Vec<Key>,
+    enemies: Vec<Enemy>,
+    crates: Vec<Idx2>,
+    plates: Vec<(Idx2, u32)>,
+    springs: Vec<Idx2>,
+    portals: Vec<(Idx2, WarpColor)>,
+    gravity_zones: HashMap<Idx2, GravityZone>,
+    gravity_switches: Vec<HbId>,
+    water: Vec<Idx2>,
 }
 
 impl GameBoardBuilder {
@@ -53,22 +84,37 @@ impl GameBoardBuilder {
             id_gen: IdGen::new(),
             collider: Collider::new(),
             room_dims,
-            player: None,
+            start_pos: None,
             star: None,
             grid: HashMap::new(),
             platforms: Vec::new(),
+            dormant_platforms: Vec::new(),
             buttons: HashMap::new(),
+            groups: HashMap::new(),
+            chirp_blocks: Vec::new(),
+            logic: Logic::new(),
             warps: Vec::new(),
             respawns: HashMap::new(),
+            respawn_stations: Vec::new(),
             lasors: Vec::new(),
+            pickups: Vec::new(),
+            keys: Vec::new(),
+            enemies: Vec::new(),
+            crates: Vec::new(),
+            plates: Vec::new(),
+            springs: Vec::new(),
+            portals: Vec::new(),
+            gravity_zones: HashMap::new(),
+            gravity_switches: Vec::new(),
+            water: Vec::new(),
         }
     }
 
-    fn button_mut(&mut self, index: u32) -> &mut (Option<Idx2>, ButtonAction) {
-        self.buttons.entry(index).or_insert((None, ButtonAction { unlock_cells: Vec::new(), platforms: Vec::new() }))
+    fn group_mut(&mut self, index: u32) -> &mut Group {
+        self.groups.entry(index).or_insert_with(Group::new)
     }
 
-    pub fn add_player(&mut self, pos: Idx2) { self.player = Some(PlayerEnum::Start(idx_to_vec(pos))); }
+    pub fn add_player(&mut self, pos: Idx2) { self.start_pos = Some(idx_to_vec(pos)); }
     pub fn add_star(&mut self, pos: Idx2) {
         let (star, hitbox) = Star::new(self.id_gen.next(), pos);
         let overlaps = self.collider.add_hitbox(PieceProfile::new(star.id(), PieceKind::Star), hitbox);
@@ -78,30 +124,78 @@ impl GameBoardBuilder {
 
     pub fn add_wall(&mut self, pos: Idx2) { self.grid.insert(pos, PendingCell::Wall); }
     pub fn add_floor(&mut self, pos: Idx2) { self.grid.insert(pos, PendingCell::Floor); }
+    pub fn add_conveyor(&mut self, pos: Idx2, dir: HorizDir) { self.grid.insert(pos, PendingCell::Conveyor(dir)); }
+    pub fn add_material(&mut self, pos: Idx2, material: Material) { self.grid.insert(pos, PendingCell::Material(material)); }
+
+    pub fn add_platform(&mut self, pos: Idx2, kind: PlatformKind, material: Material, index: Option<u32>) {
+        self.add_moving_piece(pos, kind, PlatformStyle::Floor(material), index);
+    }
+
+    pub fn add_block(&mut self, pos: Idx2, kind: PlatformKind, index: Option<u32>) {
+        self.add_moving_piece(pos, kind, PlatformStyle::Block, index);
+    }
 
-    pub fn add_platform(&mut self, pos: Idx2, kind: PlatformKind, index: Option<u32>) {
+    fn add_moving_piece(&mut self, pos: Idx2, kind: PlatformKind, style: PlatformStyle, index: Option<u32>) {
         if let Some(index) = index {
-            self.button_mut(index).1.platforms.push((pos, kind));
+            self.group_mut(index).platforms.push((pos, kind, style));
         } else {
-            self.platforms.push((pos, kind));
+            self.platforms.push((pos, kind, style));
         }
     }
 
+    // only platforms that are not controlled by the level's logic may be dormant
+    pub fn set_dormant(&mut self, pos: Idx2) { self.dormant_platforms.push(pos); }
+
     pub fn add_gate(&mut self, pos: Idx2, index: u32) {
         self.grid.insert(pos, PendingCell::Gate);
-        self.button_mut(index).1.unlock_cells.push(pos);
+        self.group_mut(index).gates.push(pos);
+    }
+
+    // chirp blocks that start off are left out of the grid until the player chirps near them
+    pub fn add_chirp_block(&mut self, pos: Idx2, on: bool) {
+        if on { self.grid.insert(pos, PendingCell::ChirpBlock); }
+        self.chirp_blocks.push(pos);
     }
 
-    pub fn add_button(&mut self, pos: Idx2, index: u32) { self.button_mut(index).0 = Some(pos) }
+    pub fn add_button(&mut self, pos: Idx2, index: u32, kind: ButtonKind) { self.buttons.insert(index, (pos, kind)); }
+    pub fn add_plate(&mut self, pos: Idx2, index: u32) { self.plates.push((pos, index)); }
+
+    pub fn set_logic(&mut self, logic: Logic) { self.logic = logic; }
 
     pub fn add_warp(&mut self, pos: Idx2, color: WarpColor) { self.warps.push((pos, color)); }
+    // the first respawn station of each color starts out active
     pub fn add_respawn(&mut self, pos: Idx2, color: WarpColor) {
-        self.grid.insert(pos, PendingCell::Spawn(color, false));
-        self.grid.insert((pos.0 + 1, pos.1), PendingCell::Spawn(color, true));
-        self.respawns.insert(color, idx_to_vec(pos) + v2(0.5 * CELL_LEN as f64, 11.));
+        let active = !self.respawns.contains_key(&color);
+        self.grid.insert(pos, PendingCell::Spawn(color, false, active));
+        self.grid.insert((pos.0 + 1, pos.1), PendingCell::Spawn(color, true, active));
+        if active { self.respawns.insert(color, cell::respawn_pos(pos)); }
+        self.respawn_stations.push((pos, color));
+    }
+
+    pub fn add_lasor(&mut self, pos: Idx2, kind: LasorKind, color: WarpColor, schedule: LasorSchedule) {
+        assert!(kind != LasorKind::Beam || schedule.fits_beam(), "beam duration does not fit in period at {:?}", pos);
+        self.lasors.push((pos, kind, color, schedule));
     }
 
-    pub fn add_lasor(&mut self, pos: Idx2, kind: LasorKind, color: WarpColor) { self.lasors.push((pos, kind, color)); }
+    pub fn add_pickup(&mut self, pos: Idx2, ability: Ability) { self.pickups.push(Pickup::new(ability, pos)); }
+    pub fn add_key(&mut self, pos: Idx2, color: KeyColor) { self.keys.push(Key::new(color, pos)); }
+    pub fn add_door(&mut self, pos: Idx2, color: KeyColor) { self.grid.insert(pos, PendingCell::Door(color)); }
+    pub fn add_enemy(&mut self, pos: Idx2, kind: EnemyKind) { self.enemies.push(Enemy::new(kind, pos)); }
+    pub fn add_crate(&mut self, pos: Idx2) { self.crates.push(pos); }
+
+    pub fn add_spring(&mut self, pos: Idx2) { self.springs.push(pos); }
+    pub fn add_portal(&mut self, pos: Idx2, color: WarpColor) { self.portals.push((pos, color)); }
+
+    pub fn add_water(&mut self, pos: Idx2) { self.water.push(pos); }
+
+    pub fn add_gravity_zone(&mut self, pos: Idx2, zone: GravityZone) { self.gravity_zones.insert(pos, zone); }
+    pub fn add_gravity_switch(&mut self, pos: Idx2) {
+        let id = self.id_gen.next();
+        let hitbox = Shape::circle(9.).place(idx_to_vec(pos)).still();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::GravitySwitch), hitbox);
+        assert!(overlaps.is_empty(), "unexpected overlap with gravity switch");
+        self.gravity_switches.push(id);
+    }
 
     pub fn build(mut self) -> GameBoard {
         let mut grid_positions: Vec<_> = self.grid.keys().cloned().collect();
@@ -111,33 +205,100 @@ impl GameBoardBuilder {
 
         let mut builder_buttons = HashMap::new();
         mem::swap(&mut self.buttons, &mut builder_buttons);
-        let buttons = builder_buttons.drain().map(|(_, (pos, action))| {
-            let id = self.form_button(pos.expect("button position must be set"));
-            (id, action)
+        for index in self.logic.timer_indices() {
+            let timed = builder_buttons.get(&index).map(|&(_, kind)| kind) == Some(ButtonKind::Timed);
+            assert!(timed, "timer {} has no timed button", index);
+        }
+        let buttons = builder_buttons.drain().map(|(index, (pos, kind))| {
+            self.logic.add_button_rules(index);
+            let timer_duration = self.logic.timer_duration(index).unwrap_or(button::DEFAULT_TIMER_DURATION);
+            (self.form_button(pos), Button::new(index, kind, timer_duration))
+        }).collect();
+        let mut builder_plates = Vec::new();
+        mem::swap(&mut self.plates, &mut builder_plates);
+        let plates = builder_plates.drain(..).map(|(pos, index)| {
+            self.logic.add_plate_rules(index);
+            (self.form_plate(pos), Plate::new(index))
         }).collect();
+        for (&index, group) in self.groups.iter() {
+            let controlled = |target| self.logic.controls(target);
+            assert!(group.gates.is_empty() || controlled(Target::Gates(index)), "gates {} have no button or logic", index);
+            assert!(group.platforms.is_empty() || controlled(Target::Platforms(index)), "platforms {} have no button or logic", index);
+        }
 
         let mut builder_lasors = Vec::new();
         mem::swap(&mut self.lasors, &mut builder_lasors);
-        let lasors = builder_lasors.drain(..).map(|(pos, kind, color)| self.form_lasor(pos, kind, color)).collect();
+        let lasors = builder_lasors.drain(..).map(|(pos, kind, color, schedule)| self.form_lasor(pos, kind, color, schedule)).collect();
+
+        let mut builder_springs = Vec::new();
+        mem::swap(&mut self.springs, &mut builder_springs);
+        let springs = builder_springs.drain(..).map(|pos| self.form_spring(pos)).collect();
 
+        let mut builder_portals = Vec::new();
+        mem::swap(&mut self.portals, &mut builder_portals);
+        let mut portal_counts = HashMap::new();
+        for &(_, color) in &builder_portals { *portal_counts.entry(color).or_insert(0) += 1; }
+        assert!(portal_counts.values().all(|&count| count == 2), "portals must come in pairs of the same color");
+        let portals = builder_portals.drain(..).map(|(pos, color)| self.form_portal(pos, color)).collect();
+
+        let mut builder_water = Vec::new();
+        mem::swap(&mut self.water, &mut builder_water);
+        let water = builder_water.iter().map(|&pos| self.form_water(pos, &builder_water)).collect();
+
+        let start_pos = self.start_pos.expect("player position must be set");
+        let query_id = self.id_gen.next();
         let mut board = GameBoard {
             id_gen: self.id_gen,
+            query_id,
             collider: self.collider,
             move_dir: None,
-            player: self.player.unwrap(),
+            player: PlayerEnum::Start(start_pos),
+            start_pos,
             star: self.star.unwrap(),
             grid,
             room_dims: self.room_dims,
             platforms: HashMap::new(),
             step_queue: StepQueue::new(),
             buttons,
+            groups: self.groups,
+            pending_gates: Vec::new(),
+            chirp_blocks: self.chirp_blocks,
+            logic: self.logic,
+            music_on: true,
             effects: Vec::new(),
             warps: HashMap::new(),
+            standing_warps: self.warps.iter().map(|&(pos, color)| (idx_to_vec(pos), color)).collect(),
             respawns: self.respawns,
+            respawn_stations: self.respawn_stations,
             lasors,
+            pickups: HashMap::new(),
+            keys: HashMap::new(),
+            spent_pickups: Vec::new(),
+            enemies: HashMap::new(),
+            defeated_enemies: Vec::new(),
+            crates: HashMap::new(),
+            plates,
+            springs,
+            portals,
+            portal_ready_time: f64::NEG_INFINITY,
+            gravity_flipped: false,
+            gravity_zones: self.gravity_zones,
+            gravity_switches: self.gravity_switches,
+            water,
+            physics: PhysicsProfile::standard(),
         };
-        for (pos, kind) in self.platforms.drain(..) { board.add_platform(pos, kind); }
-        for (pos, color) in self.warps.drain(..) { board.add_warp(idx_to_vec(pos), color, Vec2::zero(), None); }
+        for &pos in &self.dormant_platforms {
+            assert!(self.platforms.iter().any(|&(p, _, _)| p == pos), "dormant platform at {:?} is not an uncontrolled platform", pos);
+        }
+        for (pos, kind, style) in self.platforms.drain(..) {
+            let dormant = self.dormant_platforms.contains(&pos);
+            board.add_platform(pos, kind, style, dormant);
+        }
+        for pickup in self.pickups.drain(..) { board.add_pickup(pickup); }
+        for key in self.keys.drain(..) { board.add_key(key); }
+        for enemy in self.enemies.drain(..) { board.add_enemy(enemy); }
+        for pos in self.crates.drain(..) { board.add_crate(Crate::new(pos)); }
+        for (pos, color) in self.warps.drain(..) { board.add_warp(idx_to_vec(pos), Warp::standing(color), Vec2::zero(), None); }
         board
     }
 
@@ -150,12 +311,59 @@ impl GameBoardBuilder {
         id
     }
 
-    fn form_lasor(&mut self, pos: Idx2, kind: LasorKind, color: WarpColor) -> Lasor {
+    fn form_plate(&mut self, pos: Idx2) -> HbId {
+        let id = self.id_gen.next();
+        let hitbox = plate::shape(pos).still();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Plate), hitbox);
+        assert!(overlaps.is_empty(), "unexpected overlap with pressure plate");
+        id
+    }
+
+    fn form_lasor(&mut self, pos: Idx2, kind: LasorKind, color: WarpColor, schedule: LasorSchedule) -> Lasor {
+        let card = self.wall_facing_card(pos).expect("lasor was not adjacent to a wall");
+        Lasor::new(pos, kind, color, card, schedule)
+    }
+
+    fn form_spring(&mut self, pos: Idx2) -> (HbId, Spring) {
+        let card = self.wall_facing_card(pos).expect("spring was not adjacent to a wall");
+        let spring = Spring::new(pos, card);
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Spring), spring.hitbox());
+        assert!(overlaps.is_empty(), "unexpected overlap with spring");
+        (id, spring)
+    }
+
+    fn form_portal(&mut self, pos: Idx2, color: WarpColor) -> (HbId, Portal) {
+        let card = self.wall_facing_card(pos).expect("portal was not adjacent to a wall");
+        let portal = Portal::new(color, pos, card);
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Portal), portal.hitbox());
+        assert!(overlaps.is_empty(), "unexpected overlap with portal");
+        (id, portal)
+    }
+
+    fn form_water(&mut self, pos: Idx2, water: &[Idx2]) -> WaterCell {
+        let cell = WaterCell::new(pos, !water.contains(&(pos.0, pos.1 + 1)));
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Water), cell.hitbox());
+        assert!(overlaps.is_empty(), "unexpected overlap with water");
+        cell
+    }
+
+    // direction facing away from an adjacent wall, for pieces that are attached to walls;
+    // gates, doors, and chirp blocks may disappear, so nothing can be attached to them
+    fn wall_facing_card(&mut self, pos: Idx2) -> Option<Card> {
         let all_cards = Card::values();
-        let card = all_cards.iter().cloned().find(|&c| {
-            self.neighbor(pos, card_offset(c.flip())) == Some(PendingCell::Wall)
-        }).expect("lasor was not adjacent to a wall");
-        Lasor::new(pos, kind, color, card)
+        let neighbors: Vec<_> = all_cards.iter().map(|&c| (c, self.neighbor(pos, card_offset(c.flip())))).collect();
+        let card = neighbors.iter().find(|&&(_, cell)| {
+            matches!(cell, Some(PendingCell::Wall) | Some(PendingCell::Spawn(..)) | Some(PendingCell::Conveyor(_)) |
+                           Some(PendingCell::Material(_)))
+        }).map(|&(c, _)| c);
+        let removable = neighbors.iter().any(|&(_, cell)| {
+            matches!(cell, Some(PendingCell::Gate) | Some(PendingCell::ChirpBlock) | Some(PendingCell::Door(_)))
+        });
+        if card.is_none() && removable { panic!("piece at {:?} is attached to a cell that can be removed", pos); }
+        card
     }
 
     fn add_border(&mut self, right: bool) {
@@ -177,7 +385,7 @@ impl GameBoardBuilder {
                 let neighbor_offsets = [(-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0)];
                 for (idx, &offset) in neighbor_offsets.iter().enumerate() {
                     neighbors[idx] = match self.neighbor(pos, offset) {
-                        Some(PendingCell::Wall) | Some(PendingCell::Spawn(_, _)) => true,
+                        Some(PendingCell::Wall) | Some(PendingCell::Spawn(..)) => true,
                         _ => false,
                     }
                 }
@@ -188,7 +396,11 @@ impl GameBoardBuilder {
                 Cell::floor(id, neighbors)
             },
             PendingCell::Gate => Cell::gate(id),
-            PendingCell::Spawn(color, mirror) => Cell::spawn(id, color, mirror),
+            PendingCell::ChirpBlock => Cell::chirp_block(id),
+            PendingCell::Door(color) => Cell::door(id, color),
+            PendingCell::Spawn(color, mirror, active) => Cell::spawn(id, color, mirror, active),
+            PendingCell::Conveyor(dir) => Cell::conveyor(id, dir),
+            PendingCell::Material(material) => Cell::material(id, material),
         };
         let hitbox = Shape::square(CELL_LEN as f64).place(idx_to_vec(pos)).still();
         let overlaps = self.collider.add_hitbox(PieceProfile::cell(id, pos, cell.kind()), hitbox);
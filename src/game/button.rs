@@ -20,19 +20,63 @@ use collider::geom::{Shape, PlacedShape, v2};
 
 use crate::asset_id::{AssetId, SpriteId};
 use super::{CELL_LEN, Idx2};
-use super::platform::PlatformKind;
 use super::util::idx_to_vec;
 
 const WIDTH: f64 = CELL_LEN as f64 - 0.1;
 const HEIGHT: f64 = 1.;
 const Y_OFFSET: f64 = -0.5 * CELL_LEN as f64 + 0.5 * HEIGHT;
+pub const DEFAULT_TIMER_DURATION: f64 = 5.;
+const TIMER_BLINK_DURATION: f64 = 1.;
+const TIMER_BLINK_PERIOD: f64 = 0.2;
 
-pub struct ButtonAction { pub unlock_cells: Vec<Idx2>, pub platforms: Vec<(Idx2, PlatformKind)> }
+// once buttons stay open and disappear after being pressed, toggle buttons open and close on each press,
+// and timed buttons close again a while after being pressed
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ButtonKind { Once, Toggle, Timed }
 
-pub fn shape(pos: Idx2) -> PlacedShape {
-    Shape::rect(v2(WIDTH, HEIGHT)).place(idx_to_vec(pos) + v2(0., Y_OFFSET))
+// the index of a button is its signal in the level's logic
+pub struct Button { pub index: u32, pub kind: ButtonKind, pub open: bool, timer_duration: f64, lock_time: f64 }
+
+impl Button {
+    // timer_duration is only used by timed buttons
+    pub fn new(index: u32, kind: ButtonKind, timer_duration: f64) -> Button {
+        Button { index, kind, open: false, timer_duration, lock_time: f64::INFINITY }
+    }
+
+    pub fn press(&mut self, time: f64) {
+        self.open = match self.kind {
+            ButtonKind::Once | ButtonKind::Timed => true,
+            ButtonKind::Toggle => !self.open,
+        };
+        if self.kind == ButtonKind::Timed { self.lock_time = time + self.timer_duration; }
+    }
+
+    // once buttons are removed from the board after being pressed, but still hold their signal open
+    pub fn is_removed(&self) -> bool { self.kind == ButtonKind::Once && self.open }
+
+    pub fn check_timer(&mut self, time: f64) {
+        if time >= self.lock_time {
+            self.open = false;
+            self.lock_time = f64::INFINITY;
+        }
+    }
+
+    // timed buttons blink near the end of their timer
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
+        let remaining = self.lock_time - time;
+        let blink = remaining < TIMER_BLINK_DURATION && remaining % TIMER_BLINK_PERIOD < 0.5 * TIMER_BLINK_PERIOD;
+        let pressed = self.open && !blink;
+        let tile = match (self.kind, pressed) {
+            (ButtonKind::Once, _) => SpriteId::TileR1C2,
+            (ButtonKind::Toggle, false) => SpriteId::TileR4C2,
+            (ButtonKind::Toggle, true) => SpriteId::TileR5C0,
+            (ButtonKind::Timed, false) => SpriteId::TileR4C3,
+            (ButtonKind::Timed, true) => SpriteId::TileR5C1,
+        };
+        renderer.draw(&affine.pre_translate(0., -Y_OFFSET), tile);
+    }
 }
 
-pub fn draw(renderer: &mut SpriteRenderer<AssetId>, affine: Affine) {
-    renderer.draw(&affine.pre_translate(0., -Y_OFFSET), SpriteId::TileR1C2);
+pub fn shape(pos: Idx2) -> PlacedShape {
+    Shape::rect(v2(WIDTH, HEIGHT)).place(idx_to_vec(pos) + v2(0., Y_OFFSET))
 }
@@ -17,10 +17,18 @@
 use gate::renderer::{SpriteRenderer, Affine};
 
 use collider::HbId;
-use collider::geom::Vec2;
+use collider::geom::{Vec2, v2};
 
 use crate::asset_id::{AssetId, SpriteId};
+use crate::game_input::HorizDir;
 use super::warp::WarpColor;
+use super::key::KeyColor;
+use super::util::idx_to_vec;
+use super::{Idx2, CELL_LEN};
+use super::surface::{Material, Surface};
+
+const CONVEYOR_SPEED: f64 = 30.;
+const CONVEYOR_FRAMES: [SpriteId; 4] = [SpriteId::TileR3C0, SpriteId::TileR3C1, SpriteId::TileR3C2, SpriteId::TileR3C3];
 
 #[derive(Copy, Clone)]
 enum CellTransform { Id, Turn90, Turn180, Turn270, Mirror }
@@ -28,13 +36,26 @@ enum CellTransform { Id, Turn90, Turn180, Turn270, Mirror }
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum CellKind { Wall, Floor }
 
-pub struct Cell { id: HbId, kind: CellKind, tile: SpriteId, transform: CellTransform }
+pub struct Cell {
+    id: HbId,
+    kind: CellKind,
+    tile: SpriteId,
+    transform: CellTransform,
+    surface_speed: f64,
+    material: Material,
+    door: Option<KeyColor>,
+    spawn: Option<WarpColor>,
+}
 
 impl Cell {
+    fn plain(id: HbId, kind: CellKind, tile: SpriteId, transform: CellTransform) -> Cell {
+        Cell { id, kind, tile, transform, surface_speed: 0., material: Material::Normal, door: None, spawn: None }
+    }
+
     // neighbors flags start at the top-left neighbor and circles clockwise
     pub fn wall(id: HbId, neighbors: [bool; 8]) -> Cell {
         let (tile, transform) = wall_tile_and_transform(neighbors);
-        Cell { id, kind: CellKind::Wall, tile, transform }
+        Cell::plain(id, CellKind::Wall, tile, transform)
     }
 
     // neighbors flags are for left and right neighbors respectively
@@ -45,27 +66,65 @@ impl Cell {
             (true, true) => (SpriteId::TileR0C1, CellTransform::Id),
             _ => panic!("no suitable floor tile to display given surrounding tiles"),
         };
-        Cell { id, kind: CellKind::Floor, tile, transform }
+        Cell::plain(id, CellKind::Floor, tile, transform)
     }
 
     pub fn gate(id: HbId) -> Cell {
-        Cell { id, kind: CellKind::Wall, tile: SpriteId::TileR1C3, transform: CellTransform::Id }
+        Cell::plain(id, CellKind::Wall, SpriteId::TileR1C3, CellTransform::Id)
     }
 
-    pub fn spawn(id: HbId, color: WarpColor, mirrored: bool) -> Cell {
-        let tile = match color {
-            WarpColor::Green => SpriteId::TileR2C0,
-            WarpColor::Blue => SpriteId::TileR2C1,
-            WarpColor::Pink => SpriteId::TileR2C2,
-        };
+    pub fn chirp_block(id: HbId) -> Cell {
+        Cell::plain(id, CellKind::Wall, SpriteId::TileR6C0, CellTransform::Id)
+    }
+
+    // locked doors are opened by a key of the same color
+    pub fn door(id: HbId, color: KeyColor) -> Cell {
+        Cell { door: Some(color), ..Cell::plain(id, CellKind::Wall, color.door_tex(), CellTransform::Id) }
+    }
+
+    // each respawn station is a pair of spawn cells, with the right cell mirrored;
+    // only the active station of each color shows its color
+    pub fn spawn(id: HbId, color: WarpColor, mirrored: bool, active: bool) -> Cell {
         let transform = if mirrored { CellTransform::Mirror } else { CellTransform::Id };
-        Cell { id, kind: CellKind::Wall, tile, transform }
+        let mut cell = Cell { spawn: Some(color), ..Cell::plain(id, CellKind::Wall, SpriteId::TileR6C2, transform) };
+        cell.set_spawn_active(active);
+        cell
+    }
+
+    pub fn set_spawn_active(&mut self, active: bool) {
+        self.tile = match self.spawn {
+            _ if !active => SpriteId::TileR6C2,
+            Some(WarpColor::Green) => SpriteId::TileR2C0,
+            Some(WarpColor::Blue) => SpriteId::TileR2C1,
+            Some(WarpColor::Pink) => SpriteId::TileR2C2,
+            None => panic!("only spawn cells can be activated"),
+        };
+    }
+
+    // conveyor belt frames are drawn moving rightward, and mirrored for leftward belts
+    pub fn conveyor(id: HbId, dir: HorizDir) -> Cell {
+        let transform = if dir == HorizDir::Left { CellTransform::Mirror } else { CellTransform::Id };
+        Cell { surface_speed: dir.signum() * CONVEYOR_SPEED, ..Cell::plain(id, CellKind::Wall, CONVEYOR_FRAMES[0], transform) }
+    }
+
+    // ice and mud are drawn as standalone slabs, like conveyors
+    pub fn material(id: HbId, material: Material) -> Cell {
+        let tile = match material {
+            Material::Normal => panic!("normal material cells should be walls"),
+            Material::Ice => SpriteId::TileR4C0,
+            Material::Mud => SpriteId::TileR4C1,
+        };
+        Cell { material, ..Cell::plain(id, CellKind::Wall, tile, CellTransform::Id) }
     }
 
     pub fn id(&self) -> HbId { self.id }
     pub fn kind(&self) -> CellKind { self.kind }
+    pub fn door_color(&self) -> Option<KeyColor> { self.door }
 
-    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, pos: Vec2) {
+    // surface imparted to anything standing on top of the cell
+    pub fn surface(&self) -> Surface { Surface::new(v2(self.surface_speed, 0.), self.material) }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, pos: Vec2, time: f64) {
         let affine = Affine::translate(pos.x, pos.y);
         let affine = match self.transform {
             CellTransform::Id => affine,
@@ -74,10 +133,23 @@ impl Cell {
             CellTransform::Turn270 => affine.pre_rotate(-270_f64.to_radians()),
             CellTransform::Mirror => affine.pre_scale_axes(-1., 1.),
         };
-        renderer.draw(&affine, self.tile);
+        let tile = if self.surface_speed != 0. {
+            CONVEYOR_FRAMES[(time * CONVEYOR_SPEED) as usize % CONVEYOR_FRAMES.len()]
+        } else {
+            self.tile
+        };
+        renderer.draw(&affine, tile);
     }
 }
 
+// the player respawns standing on the middle of a respawn station, given the position of its left cell
+pub fn respawn_pos(station: Idx2) -> Vec2 { idx_to_vec(station) + v2(0.5 * CELL_LEN as f64, 11.) }
+
+// chirp blocks that are switched off are drawn as an outline of where they will appear
+pub fn draw_chirp_block_outline(renderer: &mut SpriteRenderer<AssetId>, pos: Vec2) {
+    renderer.draw(&Affine::translate(pos.x, pos.y), SpriteId::TileR6C1);
+}
+
 fn wall_tile_and_transform(neighbors: [bool; 8]) -> (SpriteId, CellTransform) {
     let transform_map = [CellTransform::Id, CellTransform::Turn90, CellTransform::Turn180, CellTransform::Turn270];
     for turns in 0..4 {
@@ -0,0 +1,44 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::geom::{Shape, PlacedShape, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::{CELL_LEN, Idx2};
+use super::util::idx_to_vec;
+use super::body::Body;
+
+// crates are slightly narrower than a cell, so that they can be pushed into one-cell gaps
+const SIZE: f64 = CELL_LEN as f64 - 0.2;
+
+// crates are put back where they started when the player dies
+pub struct Crate { start_pos: Idx2, pub body: Body }
+
+impl Crate {
+    pub fn new(start_pos: Idx2) -> Crate { Crate { start_pos, body: Body::new() } }
+
+    pub fn start_shape(&self) -> PlacedShape {
+        Shape::rect(v2(SIZE, SIZE)).place(idx_to_vec(self.start_pos) - v2(0., 0.5 * (CELL_LEN as f64 - SIZE)))
+    }
+
+    pub fn reset(&mut self) { self.body = Body::new(); }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine) {
+        renderer.draw(&affine, SpriteId::Crate);
+    }
+}
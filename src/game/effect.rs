@@ -16,7 +16,7 @@
 
 use gate::renderer::{SpriteRenderer, Affine};
 
-use collider::geom::Vec2;
+use collider::geom::{Vec2, v2};
 
 use crate::asset_id::{AssetId, SpriteId};
 use super::util::vec_to_affine;
@@ -95,3 +95,62 @@ impl InternalEffect for Puff {
 pub fn puff(pos: Vec2, start_time: f64, angle: f64) -> Effect {
     Effect { pos, start_time, value: Box::new(Puff { angle }) }
 }
+
+struct Splash;
+
+impl InternalEffect for Splash {
+    fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: &Affine, time: f64) -> bool {
+        if time < 0.35 {
+            for &vel in [v2(-25., 45.), v2(-10., 60.), v2(10., 60.), v2(25., 45.)].iter() {
+                let offset = vel * time + v2(0., -200. * time * time);
+                renderer.draw(&affine.pre_translate(offset.x, offset.y), SpriteId::Droplet);
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub fn splash(pos: Vec2, start_time: f64) -> Effect {
+    Effect { pos, start_time, value: Box::new(Splash) }
+}
+
+struct ChirpWave { radius: f64 }
+
+impl InternalEffect for ChirpWave {
+    fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: &Affine, time: f64) -> bool {
+        let ratio = time / 0.3;
+        if ratio < 1. {
+            let scale = (0.2 + 0.8 * ratio) * self.radius / 12.;
+            renderer.draw_flash(&affine.pre_scale(scale), SpriteId::ChirpWave, ratio);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// the wave sprite has a radius of 12 pixels, and is scaled out to the given radius
+pub fn chirp_wave(pos: Vec2, start_time: f64, radius: f64) -> Effect {
+    Effect { pos, start_time, value: Box::new(ChirpWave { radius }) }
+}
+
+struct Afterimage { tex: SpriteId, mirror: bool }
+
+impl InternalEffect for Afterimage {
+    fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: &Affine, time: f64) -> bool {
+        let ratio = time / 0.2;
+        if ratio < 1. {
+            let affine = if self.mirror { affine.pre_scale_axes(-1., 1.) } else { *affine };
+            renderer.draw_flash(&affine.pre_scale(1. - 0.5 * ratio), self.tex, 0.5 + 0.5 * ratio);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub fn afterimage(pos: Vec2, start_time: f64, tex: SpriteId, mirror: bool) -> Effect {
+    Effect { pos, start_time, value: Box::new(Afterimage { tex, mirror }) }
+}
@@ -0,0 +1,104 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::f64;
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::geom::{Shape, PlacedShape, Vec2, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use crate::game_input::HorizDir;
+use super::{CELL_LEN, Idx2};
+use super::util::idx_to_vec;
+
+pub const STEP_PERIOD: f64 = 1. / 30.;
+
+const WALKER_WIDTH: f64 = 10.;
+const WALKER_HEIGHT: f64 = 6.;
+const WALKER_SPEED: f64 = 20.;
+const FLYER_RADIUS: f64 = 4.;
+const FLYER_SPEED: f64 = 25.;
+const FLYER_AMPLITUDE: f64 = 6.;
+const FLYER_FREQUENCY: f64 = 3.;
+const LOOKAHEAD: f64 = 2.;
+const GRAPHIC_FRAME_DURATION: f64 = 0.15;
+const WALKER_FRAMES: [SpriteId; 2] = [SpriteId::Walker, SpriteId::Walker2];
+const FLYER_FRAMES: [SpriteId; 2] = [SpriteId::Flyer, SpriteId::Flyer2];
+
+// walkers patrol along the ground, turning at walls and ledges, and can be defeated by stomping on them,
+// while flyers patrol back and forth between walls along a sine path
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum EnemyKind { Walker, Flyer }
+
+#[derive(Copy, Clone)]
+pub struct Enemy { kind: EnemyKind, start_pos: Vec2, dir: HorizDir }
+
+impl Enemy {
+    pub fn new(kind: EnemyKind, pos: Idx2) -> Enemy {
+        let pos = match kind {
+            EnemyKind::Walker => idx_to_vec(pos) - v2(0., 0.5 * (CELL_LEN as f64 - WALKER_HEIGHT)),
+            EnemyKind::Flyer => idx_to_vec(pos),
+        };
+        Enemy { kind, start_pos: pos, dir: HorizDir::Left }
+    }
+
+    pub fn kind(&self) -> EnemyKind { self.kind }
+
+    // enemies that are brought back after the player dies start over from their original position
+    pub fn reset(&mut self) { self.dir = HorizDir::Left; }
+
+    pub fn start_shape(&self) -> PlacedShape {
+        let shape = match self.kind {
+            EnemyKind::Walker => Shape::rect(v2(WALKER_WIDTH, WALKER_HEIGHT)),
+            EnemyKind::Flyer => Shape::circle(2. * FLYER_RADIUS),
+        };
+        shape.place(self.start_pos)
+    }
+
+    // the point just in front of the enemy, which must be clear of walls for it to keep going
+    pub fn ahead(&self, shape: &PlacedShape) -> Vec2 {
+        shape.pos + v2(self.dir.signum() * (0.5 * shape.dims().x + LOOKAHEAD), 0.)
+    }
+
+    // the point just below the front of a walker, which must be on the ground for it to keep going
+    pub fn ground_ahead(&self, shape: &PlacedShape) -> Vec2 {
+        shape.pos + v2(self.dir.signum() * 0.5 * shape.dims().x, -0.5 * shape.dims().y - LOOKAHEAD)
+    }
+
+    pub fn turn(&mut self) { self.dir = self.dir.flip(); }
+
+    // returns the velocity that brings the enemy to its place on the path at the next step
+    pub fn vel(&self, pos: Vec2, next_time: f64) -> Vec2 {
+        match self.kind {
+            EnemyKind::Walker => v2(self.dir.signum() * WALKER_SPEED, 0.),
+            EnemyKind::Flyer => {
+                let target_y = self.start_pos.y + FLYER_AMPLITUDE * (next_time * FLYER_FREQUENCY).sin();
+                v2(self.dir.signum() * FLYER_SPEED, (target_y - pos.y) / STEP_PERIOD)
+            },
+        }
+    }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
+        let frame = (time / GRAPHIC_FRAME_DURATION) as usize % 2;
+        let (tex, offset) = match self.kind {
+            EnemyKind::Walker => (WALKER_FRAMES[frame], 0.5 * (CELL_LEN as f64 - WALKER_HEIGHT)),
+            EnemyKind::Flyer => (FLYER_FRAMES[frame], 0.),
+        };
+        let mirror = if self.dir == HorizDir::Right { -1. } else { 1. };
+        renderer.draw(&affine.pre_translate(0., offset).pre_scale_axes(mirror, 1.), tex);
+    }
+}
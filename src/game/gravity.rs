@@ -0,0 +1,68 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::geom::{Vec2, v2, Card};
+
+use crate::asset_id::{AssetId, SpriteId};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum GravityZone { Low, Reversed }
+
+impl GravityZone {
+    pub fn draw(self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine) {
+        let tex = match self {
+            GravityZone::Low => SpriteId::LowGravityZone,
+            GravityZone::Reversed => SpriteId::ReverseGravityZone,
+        };
+        renderer.draw(&affine, tex);
+    }
+}
+
+// gravity acting on the player, which is either downward or flipped upward, scaled by some amount
+#[derive(Copy, Clone, PartialEq)]
+pub struct Gravity { pub flipped: bool, pub scale: f64 }
+
+impl Gravity {
+    pub fn normal() -> Gravity { Gravity { flipped: false, scale: 1. } }
+
+    pub fn new(flipped: bool, zone: Option<GravityZone>, low_scale: f64) -> Gravity {
+        match zone {
+            None => Gravity { flipped, scale: 1. },
+            Some(GravityZone::Low) => Gravity { flipped, scale: low_scale },
+            Some(GravityZone::Reversed) => Gravity { flipped: !flipped, scale: 1. },
+        }
+    }
+
+    // converts between world coordinates and coordinates where gravity points toward -y (works in both directions)
+    pub fn orient(self, vec: Vec2) -> Vec2 { if self.flipped { v2(vec.x, -vec.y) } else { vec } }
+
+    pub fn orient_card(self, card: Card) -> Card {
+        match card {
+            Card::PlusY | Card::MinusY if self.flipped => card.flip(),
+            _ => card,
+        }
+    }
+
+    // the side of one-way floors that can be stood on
+    pub fn up(self) -> Card { self.orient_card(Card::PlusY) }
+}
+
+pub fn draw_switch(renderer: &mut SpriteRenderer<AssetId>, affine: Affine, flipped: bool) {
+    let affine = if flipped { affine.pre_scale_axes(1., -1.) } else { affine };
+    renderer.draw(&affine, SpriteId::GravitySwitch);
+}
@@ -0,0 +1,108 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::Hitbox;
+use collider::geom::{Shape, Vec2, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::Idx2;
+use super::util::{idx_to_vec, vec_to_affine};
+
+const TRAIL_SPACING: f64 = 6.;
+const HUD_SPACING: f64 = 9.;
+const HUD_MARGIN: f64 = 6.;
+
+// a key opens any one locked door of the same color
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum KeyColor { Gold, Silver, Red }
+
+impl KeyColor {
+    pub fn from_index(index: u32) -> Option<KeyColor> {
+        match index {
+            0 => Some(KeyColor::Gold),
+            1 => Some(KeyColor::Silver),
+            2 => Some(KeyColor::Red),
+            _ => None,
+        }
+    }
+
+    fn key_tex(self) -> SpriteId {
+        match self {
+            KeyColor::Gold => SpriteId::GoldKey,
+            KeyColor::Silver => SpriteId::SilverKey,
+            KeyColor::Red => SpriteId::RedKey,
+        }
+    }
+
+    pub fn door_tex(self) -> SpriteId {
+        match self {
+            KeyColor::Gold => SpriteId::GoldDoor,
+            KeyColor::Silver => SpriteId::SilverDoor,
+            KeyColor::Red => SpriteId::RedDoor,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Key { color: KeyColor, pos: Vec2 }
+
+impl Key {
+    pub fn new(color: KeyColor, pos: Idx2) -> Key { Key { color, pos: idx_to_vec(pos) } }
+
+    pub fn hitbox(&self) -> Hitbox { Shape::circle(7.).place(self.pos).still() }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
+        renderer.draw(&affine.pre_translate(0., (4. * time).sin()), self.color.key_tex());
+    }
+}
+
+// keys carried by the player, kept through warps; on death they are returned to where they were found
+#[derive(Clone)]
+pub struct Inventory { keys: Vec<Key> }
+
+impl Inventory {
+    pub fn empty() -> Inventory { Inventory { keys: Vec::new() } }
+
+    pub fn add_key(&mut self, key: Key) { self.keys.push(key); }
+
+    // the most recently collected key of the color is used first
+    pub fn take_key(&mut self, color: KeyColor) -> Option<Key> {
+        let index = self.keys.iter().rposition(|k| k.color == color)?;
+        Some(self.keys.remove(index))
+    }
+
+    pub fn drain_keys(&mut self) -> Vec<Key> { self.keys.drain(..).collect() }
+
+    // keys trail behind the player, bobbing one after another
+    pub fn draw_trail(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, mirror: bool, time: f64) {
+        let dir = if mirror { 1. } else { -1. };
+        for (index, key) in self.keys.iter().enumerate() {
+            let index = index as f64;
+            let offset = v2(dir * TRAIL_SPACING * (index + 1.), 2. + (4. * time - index).sin());
+            renderer.draw(&affine.pre_translate(offset.x, offset.y), key.color.key_tex());
+        }
+    }
+
+    // keys are listed along the top-left corner of the screen
+    pub fn draw_hud(&self, renderer: &mut SpriteRenderer<AssetId>, screen_height: f64) {
+        for (index, key) in self.keys.iter().enumerate() {
+            let pos = v2(HUD_MARGIN + HUD_SPACING * index as f64, screen_height - HUD_MARGIN);
+            renderer.draw(&vec_to_affine(pos), key.color.key_tex());
+        }
+    }
+}
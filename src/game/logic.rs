@@ -0,0 +1,253 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use collider::HbId;
+
+use super::Idx2;
+use super::platform::{PlatformKind, PlatformStyle};
+use super::warp::WarpColor;
+
+// pieces sharing an index in a level's index file, which are controlled together by the level's logic
+pub struct Group {
+    pub gates: Vec<Idx2>,
+    pub platforms: Vec<(Idx2, PlatformKind, PlatformStyle)>,
+    pub platform_ids: Vec<HbId>,
+}
+
+impl Group {
+    pub fn new() -> Group { Group { gates: Vec::new(), platforms: Vec::new(), platform_ids: Vec::new() } }
+}
+
+// the state of the level that the logic's signals are read from
+pub struct Inputs {
+    pub time: f64,
+    pub star: bool,
+    pub player_idx: Idx2,
+    pub open_buttons: Vec<u32>,
+    pub pressed_plates: Vec<u32>,
+}
+
+enum Signal { Button(u32), Plate(u32), Star, Time(f64), Every(f64), Region(Idx2, Idx2) }
+
+impl Signal {
+    fn eval(&self, inputs: &Inputs) -> bool {
+        match *self {
+            Signal::Button(index) => inputs.open_buttons.contains(&index),
+            Signal::Plate(index) => inputs.pressed_plates.contains(&index),
+            Signal::Star => inputs.star,
+            Signal::Time(time) => inputs.time >= time,
+            Signal::Every(period) => (inputs.time / period) % 2. >= 1.,
+            Signal::Region(min, max) => {
+                let (x, y) = inputs.player_idx;
+                x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1
+            },
+        }
+    }
+}
+
+// a sequence becomes true once each of its expressions has been true in order, and then stays true
+enum Expr { Signal(Signal), Not(Box<Expr>), And(Vec<Expr>), Or(Vec<Expr>), Seq(Vec<Expr>, usize) }
+
+impl Expr {
+    fn eval(&mut self, inputs: &Inputs) -> bool {
+        match *self {
+            Expr::Signal(ref signal) => signal.eval(inputs),
+            Expr::Not(ref mut expr) => !expr.eval(inputs),
+            // every sub-expression is evaluated so that nested sequences keep track of their progress
+            Expr::And(ref mut exprs) => exprs.iter_mut().map(|expr| expr.eval(inputs)).filter(|&value| !value).count() == 0,
+            Expr::Or(ref mut exprs) => exprs.iter_mut().map(|expr| expr.eval(inputs)).filter(|&value| value).count() > 0,
+            Expr::Seq(ref mut exprs, ref mut progress) => {
+                while *progress < exprs.len() && exprs[*progress].eval(inputs) { *progress += 1; }
+                *progress == exprs.len()
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Target { Gates(u32), Platforms(u32), Lasors(WarpColor), Warps(WarpColor), Music }
+
+impl Target {
+    // gates start closed and platforms start unspawned, while everything else starts enabled
+    fn initial_value(self) -> bool {
+        match self {
+            Target::Gates(_) | Target::Platforms(_) => false,
+            Target::Lasors(_) | Target::Warps(_) | Target::Music => true,
+        }
+    }
+}
+
+struct Rule { target: Target, expr: Expr, value: bool }
+
+pub struct Logic { rules: Vec<Rule>, timers: HashMap<u32, f64> }
+
+impl Logic {
+    pub fn new() -> Logic { Logic { rules: Vec::new(), timers: HashMap::new() } }
+
+    // parses lines of the form "target = expression", for example
+    //   gates 2 = and(button 0, not(region 4 1 8 3))
+    //   platforms 3 = seq(button 1, button 2)
+    //   lasors 0 = every 2.5
+    // targets are "gates N", "platforms N", "lasors N", "warps N", and "music", where the index of
+    // lasors and warps is their color, and signals are "button N", "plate N", "star", "time SECONDS",
+    // "every SECONDS", and "region MIN_X MIN_Y MAX_X MAX_Y", in cells from the bottom-left of the level;
+    // lines of the form "timer N = SECONDS" instead set how long the timed button N stays open
+    pub fn parse(contents: &str) -> Result<Logic, String> {
+        let mut logic = Logic::new();
+        for (line_num, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.starts_with("timer ") {
+                let (index, duration) = parse_timer(line).map_err(|err| format!("line {}: {}", line_num + 1, err))?;
+                if logic.timers.insert(index, duration).is_some() { return Err(format!("line {}: timer is already set", line_num + 1)); }
+            } else if !line.is_empty() {
+                let (target, expr) = parse_rule(line).map_err(|err| format!("line {}: {}", line_num + 1, err))?;
+                if logic.controls(target) { return Err(format!("line {}: target is already controlled", line_num + 1)); }
+                logic.rules.push(Rule { target, expr, value: target.initial_value() });
+            }
+        }
+        Ok(logic)
+    }
+
+    pub fn timer_duration(&self, index: u32) -> Option<f64> { self.timers.get(&index).cloned() }
+
+    pub fn timer_indices(&self) -> Vec<u32> { self.timers.keys().cloned().collect() }
+
+    pub fn controls(&self, target: Target) -> bool { self.rules.iter().any(|r| r.target == target) }
+
+    // buttons control the gates and platforms sharing their index, unless the level says otherwise
+    pub fn add_button_rules(&mut self, index: u32) { self.add_default_rules(index, Signal::Button); }
+
+    // pressure plates do the same, unless a button already shares their index
+    pub fn add_plate_rules(&mut self, index: u32) { self.add_default_rules(index, Signal::Plate); }
+
+    fn add_default_rules(&mut self, index: u32, signal: fn(u32) -> Signal) {
+        for &target in [Target::Gates(index), Target::Platforms(index)].iter() {
+            if !self.controls(target) {
+                let expr = Expr::Signal(signal(index));
+                self.rules.push(Rule { target, expr, value: target.initial_value() });
+            }
+        }
+    }
+
+    // returns the targets whose values changed
+    pub fn update(&mut self, inputs: &Inputs) -> Vec<(Target, bool)> {
+        self.rules.iter_mut().filter_map(|rule| {
+            let value = rule.expr.eval(inputs);
+            if value != rule.value {
+                rule.value = value;
+                Some((rule.target, value))
+            } else {
+                None
+            }
+        }).collect()
+    }
+}
+
+fn parse_rule(line: &str) -> Result<(Target, Expr), String> {
+    let spaced = line.replace('(', " ( ").replace(')', " ) ").replace(',', " , ");
+    let mut parser = Parser { tokens: spaced.split_whitespace().collect(), pos: 0 };
+    let target = parser.target()?;
+    parser.expect("=")?;
+    let expr = parser.expr()?;
+    match parser.next() {
+        Ok(token) => Err(format!("unexpected \"{}\" after expression", token)),
+        Err(_) => Ok((target, expr)),
+    }
+}
+
+fn parse_timer(line: &str) -> Result<(u32, f64), String> {
+    let mut parser = Parser { tokens: line.split_whitespace().collect(), pos: 0 };
+    parser.expect("timer")?;
+    let index = parser.number()?;
+    parser.expect("=")?;
+    let duration: f64 = parser.number()?;
+    if duration <= 0. { return Err(format!("timer duration must be positive but found {}", duration)); }
+    match parser.next() {
+        Ok(token) => Err(format!("unexpected \"{}\" after timer duration", token)),
+        Err(_) => Ok((index, duration)),
+    }
+}
+
+struct Parser<'a> { tokens: Vec<&'a str>, pos: usize }
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| "unexpected end of line".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token == expected { Ok(()) } else { Err(format!("expected \"{}\" but found \"{}\"", expected, token)) }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, String> {
+        let token = self.next()?;
+        token.parse().map_err(|_| format!("expected a number but found \"{}\"", token))
+    }
+
+    fn color(&mut self) -> Result<WarpColor, String> {
+        let index = self.number()?;
+        WarpColor::from_index(index).ok_or_else(|| format!("invalid color index {}", index))
+    }
+
+    fn target(&mut self) -> Result<Target, String> {
+        match self.next()? {
+            "gates" => Ok(Target::Gates(self.number()?)),
+            "platforms" => Ok(Target::Platforms(self.number()?)),
+            "lasors" => Ok(Target::Lasors(self.color()?)),
+            "warps" => Ok(Target::Warps(self.color()?)),
+            "music" => Ok(Target::Music),
+            token => Err(format!("unknown target \"{}\"", token)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let signal = match self.next()? {
+            name @ "not" | name @ "and" | name @ "or" | name @ "seq" => {
+                self.expect("(")?;
+                let mut exprs = vec![self.expr()?];
+                while self.tokens.get(self.pos) == Some(&",") {
+                    self.pos += 1;
+                    exprs.push(self.expr()?);
+                }
+                self.expect(")")?;
+                return match name {
+                    "not" if exprs.len() == 1 => Ok(Expr::Not(Box::new(exprs.pop().unwrap()))),
+                    "not" => Err("\"not\" takes a single expression".to_string()),
+                    "and" => Ok(Expr::And(exprs)),
+                    "or" => Ok(Expr::Or(exprs)),
+                    _ => Ok(Expr::Seq(exprs, 0)),
+                };
+            },
+            "button" => Signal::Button(self.number()?),
+            "plate" => Signal::Plate(self.number()?),
+            "star" => Signal::Star,
+            "time" => Signal::Time(self.number()?),
+            "every" => Signal::Every(self.number()?),
+            "region" => {
+                let min = (self.number()?, self.number()?);
+                Signal::Region(min, (self.number()?, self.number()?))
+            },
+            token => return Err(format!("unknown signal \"{}\"", token)),
+        };
+        Ok(Expr::Signal(signal))
+    }
+}
@@ -14,22 +14,36 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod ability;
 mod background;
+mod body;
 mod builder;
 mod button;
 mod cell;
+mod crates;
 mod effect;
+mod enemy;
+mod gravity;
+mod key;
+mod logic;
 mod step_queue;
 mod piece_profile;
+mod physics;
+mod plate;
 mod platform;
+mod portal;
+mod spring;
 mod player_enum;
 mod player;
 mod star;
+mod surface;
 mod util;
 mod warp;
+mod water;
 
 use std::collections::HashMap;
 use std::f64;
+use std::mem;
 
 use gate::{Audio, AppContext};
 use gate::renderer::Renderer;
@@ -38,49 +52,99 @@ use collider::{Collider, HbId, HbVel, HbEvent, HbProfile};
 use collider::geom::{v2, Vec2, Card, CardMask, Shape};
 
 use crate::game_input::{InputEvent, HorizDir};
-use crate::asset_id::{AssetId, SoundId};
+use crate::asset_id::{AssetId, SoundId, MusicId};
 use self::piece_profile::{PieceKind, PieceProfile};
-use self::player_enum::{PlayerEnum, PlayerComplete, PlayerWarping};
+use self::player_enum::{PlayerEnum, PlayerComplete, PlayerWarping, PlayerDying};
 use self::player::Player;
-use self::step_queue::{StepQueue, Step};
+use self::step_queue::{StepQueue, Step, LASOR_UPDATE_PERIOD};
 use self::cell::{Cell, CellKind};
 use self::effect::Effect;
 use self::star::Star;
-use self::platform::Platform;
-use self::button::ButtonAction;
-use self::util::{IdGen, idx_to_vec, vec_to_affine, card_offset};
-use self::warp::Lasor;
+use self::platform::{Platform, PlatformStyle};
+use self::surface::Surface;
+use self::button::Button;
+use self::logic::{Group, Target, Inputs};
+use self::util::{IdGen, idx_to_vec, vec_to_idx, vec_to_affine, card_offset, segment_entry};
+use self::warp::{Lasor, Warp, Flight};
+use self::ability::{Abilities, Pickup};
+use self::key::{Key, Inventory};
+use self::spring::Spring;
+use self::portal::Portal;
+use self::gravity::Gravity;
+use self::water::WaterCell;
+use self::enemy::Enemy;
+use self::crates::Crate;
+use self::plate::Plate;
 
 pub use self::builder::GameBoardBuilder;
 pub use self::platform::PlatformKind;
-pub use self::warp::{WarpColor, LasorKind};
+pub use self::surface::Material;
+pub use self::gravity::GravityZone;
+pub use self::button::ButtonKind;
+pub use self::logic::Logic;
+pub use self::warp::{WarpColor, LasorKind, LasorSchedule};
+pub use self::ability::Ability;
+pub use self::key::KeyColor;
+pub use self::enemy::EnemyKind;
+pub use self::physics::PhysicsProfile;
 
 pub type Idx2 = (i32, i32);
 
 const CELL_LEN: i32 = 8;
+const CHIRP_RANGE: f64 = 28.;
+const CHIRP_STUN_DURATION: f64 = 2.;
 pub const SCREEN_PIXELS_HEIGHT: f64 = CELL_LEN as f64 * 24.;
 
 pub struct GameBoard {
     id_gen: IdGen,
+    query_id: HbId,
     collider: Collider<PieceProfile>,
     move_dir: Option<HorizDir>,
     player: PlayerEnum,
+    start_pos: Vec2,
     star: Star,
     grid: HashMap<Idx2, Cell>,
     room_dims: Idx2,
     platforms: HashMap<HbId, Platform>,
     step_queue: StepQueue,
-    buttons: HashMap<HbId, ButtonAction>,
+    buttons: HashMap<HbId, Button>,
+    groups: HashMap<u32, Group>,
+    pending_gates: Vec<Idx2>,
+    chirp_blocks: Vec<Idx2>,
+    logic: Logic,
+    music_on: bool,
     effects: Vec<Effect>,
-    warps: HashMap<HbId, WarpColor>,
+    warps: HashMap<HbId, Warp>,
+    standing_warps: Vec<(Vec2, WarpColor)>,
     respawns: HashMap<WarpColor, Vec2>,
+    respawn_stations: Vec<(Idx2, WarpColor)>,
     lasors: Vec<Lasor>,
+    pickups: HashMap<HbId, Pickup>,
+    spent_pickups: Vec<Pickup>,
+    keys: HashMap<HbId, Key>,
+    enemies: HashMap<HbId, Enemy>,
+    defeated_enemies: Vec<Enemy>,
+    crates: HashMap<HbId, Crate>,
+    plates: HashMap<HbId, Plate>,
+    springs: HashMap<HbId, Spring>,
+    portals: HashMap<HbId, Portal>,
+    portal_ready_time: f64,
+    gravity_flipped: bool,
+    gravity_zones: HashMap<Idx2, GravityZone>,
+    gravity_switches: Vec<HbId>,
+    water: Vec<WaterCell>,
+    physics: PhysicsProfile,
 }
 
 impl GameBoard {
     pub fn builder(dims: Idx2) -> GameBoardBuilder { GameBoardBuilder::new(dims) }
     pub fn is_done(&self) -> bool { self.time() > self.star.level_end_time() }
 
+    pub fn set_physics(&mut self, physics: PhysicsProfile) {
+        self.physics = physics;
+        if let PlayerEnum::Normal(ref mut player) = self.player { player.set_physics(physics); }
+    }
+
     fn room_pixels(&self) -> Vec2 { v2((self.room_dims.0 * CELL_LEN) as f64, (self.room_dims.1 * CELL_LEN) as f64) }
     fn player_pos(&self) -> Vec2 { self.player.pos(&self.collider) }
     fn time(&self) -> f64 { self.collider.time() }
@@ -95,29 +159,42 @@ impl GameBoard {
                 InputEvent::UpdateMovement(dir) => player.set_movement(dir),
                 InputEvent::PressJump => player.press_jump(),
                 InputEvent::ReleaseJump => player.release_jump(),
+                InputEvent::PressDown => player.press_down(),
+                InputEvent::ReleaseDown => player.release_down(),
+                InputEvent::PressDash => player.press_dash(),
+                InputEvent::PressChirp => player.press_chirp(),
             }
         }
     }
 
     pub fn advance(&mut self, elapsed: f64, audio: &mut Audio<AssetId>) {
         let end_time = self.time() + elapsed;
-        if self.time() == 0.0 && end_time > 0.0 { audio.play_sound(SoundId::Clear); }
+        if self.time() == 0.0 && end_time > 0.0 {
+            audio.play_sound(SoundId::Clear);
+            self.update_logic(audio);
+        }
         while self.time() < end_time {
             let collider_time = self.collider.next_time();
             let event_time = self.step_queue.peek();
             let player_transition_time = self.player.transition_time();
-            let time = collider_time.min(event_time).min(end_time).min(player_transition_time);
+            let lasor_fire_time = self.next_lasor_fire_time();
+            let time = collider_time.min(event_time).min(end_time).min(player_transition_time).min(lasor_fire_time);
             self.collider.set_time(time);
             if let PlayerEnum::Normal(ref mut player) = self.player { player.set_time(time); }
             if time == event_time {
                 match self.step_queue.pop() {
                     Step::Player => self.player_step(audio),
-                    Step::Platform => self.platform_step(),
-                    Step::WarpEffectSpawn => self.warp_effect_step(),
-                    Step::LasorFire => self.lasors_step(audio),
+                    Step::Platform => self.platform_step(audio),
+                    Step::EffectSpawn => self.effect_step(),
+                    Step::Logic => self.logic_step(audio),
+                    Step::LasorUpdate => self.lasor_update_step(audio),
+                    Step::Enemy => self.enemy_step(),
+                    Step::Crate => self.crate_step(audio),
                 }
             } else if time == player_transition_time {
                 self.player_transition(audio);
+            } else if time == lasor_fire_time {
+                self.lasors_step(audio);
             } else if let Some((hb_event, p_1, p_2)) = self.collider.next() {
                 self.handle_hb_event_asym(hb_event, p_1, p_2, audio);
                 self.handle_hb_event_asym(hb_event, p_2, p_1, audio);
@@ -126,67 +203,116 @@ impl GameBoard {
     }
 
     fn player_transition(&mut self, audio: &mut Audio<AssetId>) {
-        if let PlayerEnum::Warping(_) = self.player { audio.play_sound(SoundId::Warp) }
-        let pos = self.player_pos();
-        let (player, shape) = Player::new(self.id_gen.next(), pos, self.collider.time(), self.move_dir);
+        let (pos, abilities, inventory, vel) = match self.player {
+            PlayerEnum::Warping(ref player) => {
+                audio.play_sound(SoundId::Warp);
+                (self.player_pos(), player.abilities(), player.inventory().clone(), player.exit_vel())
+            },
+            PlayerEnum::Dying(ref player) => {
+                audio.play_sound(SoundId::Warp);
+                (player.respawn_pos(), Abilities::none(), Inventory::empty(), Vec2::zero())
+            },
+            _ => (self.player_pos(), Abilities::none(), Inventory::empty(), Vec2::zero()),
+        };
+        let id = self.id_gen.next();
+        let (mut player, shape) = Player::new(id, pos, self.time(), self.move_dir, abilities, inventory, self.physics);
+        player.set_gravity(self.gravity_at(pos));
+        player.set_vel(vel);
         let hitbox = shape.still_until(self.step_queue.peek_specific(Step::Player));
         let overlaps = self.collider.add_hitbox(PieceProfile::new(player.id(), PieceKind::Player), hitbox);
-        assert!(overlaps.iter().all(|p| p.kind == PieceKind::Platform), "unexpected overlap with new player");
-        self.player = PlayerEnum::Normal(player);
+        let allowed_overlaps = [PieceKind::Platform, PieceKind::Block, PieceKind::Water, PieceKind::Portal];
+        assert!(overlaps.iter().all(|p| allowed_overlaps.contains(&p.kind)), "unexpected overlap with new player");
+        if overlaps.iter().any(|p| p.kind == PieceKind::Water) { player.set_swimming(true); }
+        self.player = PlayerEnum::Normal(Box::new(player));
     }
 
     fn handle_hb_event_asym(&mut self, event: HbEvent, p_1: PieceProfile, p_2: PieceProfile, audio: &mut Audio<AssetId>) {
         match p_1.kind {
             PieceKind::Player => match p_2.kind {
-                PieceKind::Wall | PieceKind::Floor | PieceKind::Platform => self.update_player_barriers(),
+                PieceKind::Wall if event == HbEvent::Collide && p_2.index.is_some() => self.touch_cell(p_2.index.unwrap(), audio),
+                PieceKind::Wall | PieceKind::Floor | PieceKind::Platform | PieceKind::Block |
+                PieceKind::Crate => self.update_player_barriers(audio),
+                PieceKind::Plate => self.update_plates(audio),
                 PieceKind::Button if event == HbEvent::Collide => self.press_button(p_2.id(), audio),
                 PieceKind::Warp if event == HbEvent::Collide => self.warp(p_2.id(), audio),
+                PieceKind::Beam if event == HbEvent::Collide => self.beam_hits_player(p_2.id(), audio),
+                PieceKind::Enemy if event == HbEvent::Collide => self.touch_enemy(p_2.id(), audio),
                 PieceKind::Star if event == HbEvent::Collide => self.obtain_star(audio),
+                PieceKind::Pickup if event == HbEvent::Collide => self.collect_pickup(p_2.id(), audio),
+                PieceKind::Key if event == HbEvent::Collide => self.collect_key(p_2.id(), audio),
+                PieceKind::Portal if event == HbEvent::Collide => self.enter_portal(p_2.id(), audio),
+                PieceKind::Spring if event == HbEvent::Collide => self.bounce_on_spring(p_2.id(), audio),
+                PieceKind::GravitySwitch if event == HbEvent::Collide => self.flip_gravity(p_2.id(), audio),
+                PieceKind::Water => self.update_player_swimming(),
+                _ => {},
+            },
+            PieceKind::Crate => match p_2.kind {
+                PieceKind::Wall | PieceKind::Floor | PieceKind::Platform | PieceKind::Block |
+                PieceKind::Crate => self.update_crate_barriers(p_1.id),
+                PieceKind::Plate => self.update_plates(audio),
                 _ => {},
             },
             PieceKind::Warp if event == HbEvent::Collide => match p_2.kind {
-                PieceKind::Wall => self.warp_hits_wall(p_1.id, p_2.id, CardMask::full()),
+                // internal edges between wall cells are masked so that warps reflect cleanly off flat walls
+                PieceKind::Wall if p_2.index.is_some() => {
+                    let mask = wall_card_mask(&self.grid, p_2.index.unwrap(), false);
+                    if mask != CardMask::empty() { self.warp_hits_wall(p_1.id, p_2.id, mask); }
+                },
+                PieceKind::Wall | PieceKind::Block | PieceKind::Crate => self.warp_hits_wall(p_1.id, p_2.id, CardMask::full()),
                 PieceKind::Floor | PieceKind::Platform => self.warp_hits_wall(p_1.id, p_2.id, Card::PlusY.into()),
+                PieceKind::Button => self.warp_hits_button(p_1.id, p_2.id, audio),
+                PieceKind::GravitySwitch => self.warp_hits_gravity_switch(p_1.id, p_2.id, audio),
                 _ => {},
             },
             _ => {},
         }
     }
 
-    fn update_player_barriers(&mut self) {
+    fn update_player_barriers(&mut self, audio: &mut Audio<AssetId>) {
         let is_near_ground = self.check_player_near_ground();
-        if let PlayerEnum::Normal(ref mut player) = self.player {
+        let crushed = if let PlayerEnum::Normal(ref mut player) = self.player {
             let player_shape = self.collider.get_hitbox(player.id()).value;
             let barrier_prs = self.collider.get_overlaps(player.id());
-            let (grid, collider) = (&self.grid, &self.collider);
+            let (grid, platforms, collider) = (&self.grid, &self.platforms, &self.collider);
+            let dropping = player.is_dropping();
+            let up = player.gravity().up();
             let barriers = barrier_prs.iter().filter_map(|pr| match pr.kind {
-                PieceKind::Floor | PieceKind::Platform => Some((collider.get_hitbox(pr.id), Card::PlusY.into())),
+                PieceKind::Floor if !dropping => Some((collider.get_hitbox(pr.id), up.into(), Surface::one_way(Material::Normal))),
+                PieceKind::Platform if !dropping => Some((collider.get_hitbox(pr.id), up.into(), platforms[&pr.id].surface())),
                 PieceKind::Wall if pr.index.is_some() => {
+                    let index = pr.index.unwrap();
                     let wall_hitbox = collider.get_hitbox(pr.id);
-                    let player_above_wall = player_shape.masked_normal_from(
-                        &wall_hitbox.value, Card::PlusY.into()).len() < PieceProfile::padding();
-                    let mask = wall_card_mask(grid, pr.index.unwrap(), player_above_wall);
-                    if mask == CardMask::empty() { None } else { Some((wall_hitbox, mask)) }
+                    let player_on_wall = player_shape.masked_normal_from(
+                        &wall_hitbox.value, up.into()).len() < PieceProfile::padding();
+                    let mask = wall_card_mask(grid, index, player_on_wall);
+                    if mask == CardMask::empty() { None } else { Some((wall_hitbox, mask, grid[&index].surface())) }
+                },
+                PieceKind::Wall | PieceKind::Block | PieceKind::Crate => {
+                    Some((collider.get_hitbox(pr.id), CardMask::full(), Surface::plain()))
                 },
-                PieceKind::Wall => Some((collider.get_hitbox(pr.id), CardMask::full())),
                 _ => None,
             });
             player.update_barriers(player_shape, is_near_ground, barriers);
+            player.is_crushed()
         } else {
             unreachable!();
+        };
+        if crushed {
+            self.kill_player(audio);
+        } else {
+            self.update_player_hitbox_vel();
         }
-        self.update_player_hitbox_vel();
     }
 
     fn check_player_near_ground(&self) -> bool {
         if let PlayerEnum::Normal(ref player) = self.player {
             let player_shape = self.collider.get_hitbox(player.id()).value;
             let padding = PieceProfile::padding();
-            let test_shape = Shape::rect(Vec2::zero())
-                                   .place(player_shape.pos + v2(0., -0.5 * player_shape.dims().y - padding));
+            let test_offset = player.gravity().orient(v2(0., -0.5 * player_shape.dims().y - padding));
+            let test_shape = Shape::rect(Vec2::zero()).place(player_shape.pos + test_offset);
             let mut overlaps = self.collider.query_overlaps(&test_shape, &PieceProfile::new(player.id(), PieceKind::Player));
             let result = overlaps.drain(..).any(|p| {
-                p.kind == PieceKind::Wall || p.kind == PieceKind::Floor
+                p.kind == PieceKind::Wall || (p.kind == PieceKind::Floor && !player.is_dropping())
             });
             result
         } else {
@@ -194,6 +320,43 @@ impl GameBoard {
         }
     }
 
+    fn gravity_at(&self, pos: Vec2) -> Gravity {
+        let zone = self.gravity_zones.get(&vec_to_idx(pos)).cloned();
+        Gravity::new(self.gravity_flipped, zone, self.physics.low_gravity_scale)
+    }
+
+    // returns true if the direction of gravity changed, in which case player barriers must be updated
+    fn update_player_gravity(&mut self) -> bool {
+        let gravity = self.gravity_at(self.player_pos());
+        if let PlayerEnum::Normal(ref mut player) = self.player {
+            let flipped = player.gravity().flipped != gravity.flipped;
+            player.set_gravity(gravity);
+            flipped
+        } else {
+            false
+        }
+    }
+
+    fn flip_gravity(&mut self, switch_id: HbId, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Button);
+        self.gravity_flipped = !self.gravity_flipped;
+        self.effects.push(effect::square_fade(self.hb_pos(switch_id), self.time()));
+        if self.update_player_gravity() { self.update_player_barriers(audio); }
+    }
+
+    // a splash is made whenever the player enters or leaves the water
+    fn update_player_swimming(&mut self) {
+        let pos = self.player_pos();
+        if let PlayerEnum::Normal(ref mut player) = self.player {
+            let swimming = self.collider.get_overlaps(player.id()).iter().any(|p| p.kind == PieceKind::Water);
+            if swimming != player.is_swimming() {
+                player.set_swimming(swimming);
+                self.effects.push(effect::splash(pos, self.collider.time()));
+            }
+        }
+        self.update_player_hitbox_vel();
+    }
+
     fn update_player_hitbox_vel(&mut self) {
         if let PlayerEnum::Normal(ref player) = self.player {
             let next_time = self.step_queue.peek_specific(Step::Player);
@@ -201,6 +364,16 @@ impl GameBoard {
         }
     }
 
+    fn bounce_on_spring(&mut self, spring_id: HbId, audio: &mut Audio<AssetId>) {
+        if let PlayerEnum::Normal(ref mut player) = self.player {
+            audio.play_sound(SoundId::Spring);
+            let spring = self.springs.get_mut(&spring_id).unwrap();
+            spring.launch(self.collider.time());
+            player.launch(spring.card());
+        }
+        self.update_player_hitbox_vel();
+    }
+
     fn obtain_star(&mut self, audio: &mut Audio<AssetId>) {
         let time = self.time();
         self.star.obtain(time);
@@ -214,67 +387,434 @@ impl GameBoard {
 
         self.player = PlayerEnum::Complete(PlayerComplete::new(pos, time, tex, mirror));
         audio.play_sound(SoundId::Clear);
+        self.update_logic(audio);
+        if !self.music_on { audio.loop_music(MusicId::BgMusic); }
+    }
+
+    fn kill_player(&mut self, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Death);
+        let pos = self.player_pos();
+        let (tex, mirror, held_keys) = if let PlayerEnum::Normal(ref mut player) = self.player {
+            self.collider.remove_hitbox(player.id());
+            let (tex, mirror) = player.tex_and_mirror();
+            (tex, mirror, player.inventory_mut().drain_keys())
+        } else {
+            unreachable!()
+        };
+        self.effects.push(effect::puff(pos, self.time(), 0.));
+        self.player = PlayerEnum::Dying(PlayerDying::new(pos, self.start_pos, self.time(), tex, mirror));
+        self.gravity_flipped = false;
+        let mut spent_pickups = Vec::new();
+        mem::swap(&mut self.spent_pickups, &mut spent_pickups);
+        for pickup in spent_pickups.drain(..) { self.add_pickup(pickup); }
+        for key in held_keys { self.add_key(key); }
+        let mut defeated_enemies = Vec::new();
+        mem::swap(&mut self.defeated_enemies, &mut defeated_enemies);
+        for enemy in defeated_enemies.drain(..) { self.add_enemy(enemy); }
+        let crate_ids: Vec<_> = self.crates.keys().cloned().collect();
+        for id in crate_ids {
+            self.collider.remove_hitbox(id);
+            let mut crate_piece = self.crates.remove(&id).unwrap();
+            crate_piece.reset();
+            self.add_crate(crate_piece);
+        }
+    }
+
+    // walkers are defeated by stomping on them, but otherwise touching an enemy kills the player
+    fn touch_enemy(&mut self, enemy_id: HbId, audio: &mut Audio<AssetId>) {
+        let enemy_pos = self.hb_pos(enemy_id);
+        let rel_enemy_pos = enemy_pos - self.player_pos();
+        let stomped = if let PlayerEnum::Normal(ref mut player) = self.player {
+            let stomped = self.enemies[&enemy_id].kind() == EnemyKind::Walker && player.is_stomping(rel_enemy_pos);
+            if stomped { player.stomp(audio); }
+            stomped
+        } else {
+            unreachable!()
+        };
+        if stomped {
+            self.collider.remove_hitbox(enemy_id);
+            let mut enemy = self.enemies.remove(&enemy_id).unwrap();
+            enemy.reset();
+            self.defeated_enemies.push(enemy);
+            self.effects.push(effect::puff(enemy_pos, self.time(), 0.));
+            self.update_player_hitbox_vel();
+        } else {
+            self.kill_player(audio);
+        }
+    }
+
+    fn collect_pickup(&mut self, pickup_id: HbId, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Button);
+        let pos = self.hb_pos(pickup_id);
+        self.collider.remove_hitbox(pickup_id);
+        let pickup = self.pickups.remove(&pickup_id).unwrap();
+        if let PlayerEnum::Normal(ref mut player) = self.player { player.grant_ability(pickup.ability()); } else { unreachable!() }
+        self.effects.push(effect::square_fade(pos, self.time()));
+        self.spent_pickups.push(pickup);
+    }
+
+    fn add_enemy(&mut self, enemy: Enemy) {
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Enemy), enemy.start_shape().still());
+        assert!(overlaps.iter().all(|p| p.kind != PieceKind::Player), "unexpected overlap with enemy");
+        self.enemies.insert(id, enemy);
+    }
+
+    fn add_crate(&mut self, crate_piece: Crate) {
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Crate), crate_piece.start_shape().still());
+        assert!(overlaps.iter().all(|p| p.kind != PieceKind::Player), "unexpected overlap with crate");
+        self.crates.insert(id, crate_piece);
+    }
+
+    // a key collected while already touching a locked door of its color opens the door right away
+    fn collect_key(&mut self, key_id: HbId, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Button);
+        let pos = self.hb_pos(key_id);
+        self.collider.remove_hitbox(key_id);
+        let key = self.keys.remove(&key_id).unwrap();
+        let player_id = if let PlayerEnum::Normal(ref mut player) = self.player {
+            player.inventory_mut().add_key(key);
+            player.id()
+        } else {
+            unreachable!()
+        };
+        self.effects.push(effect::square_fade(pos, self.time()));
+        let grid = &self.grid;
+        let doors: Vec<_> = self.collider.get_overlaps(player_id).iter()
+                                .filter_map(|p| p.index)
+                                .filter(|idx| grid.get(idx).and_then(|c| c.door_color()).is_some())
+                                .collect();
+        if doors.is_empty() { return; }
+        for idx in doors { self.try_open_door(idx, audio); }
+        self.update_player_barriers(audio);
+    }
+
+    fn add_key(&mut self, key: Key) {
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Key), key.hitbox());
+        assert!(overlaps.is_empty(), "unexpected overlap with key");
+        self.keys.insert(id, key);
+    }
+
+    // touching a locked door may open it, and touching a respawn station activates it
+    fn touch_cell(&mut self, pos: Idx2, audio: &mut Audio<AssetId>) {
+        if self.grid.get(&pos).and_then(|c| c.door_color()).is_some() { self.try_open_door(pos, audio); }
+        let station = self.respawn_stations.iter().cloned().find(|&(station, _)| {
+            pos == station || pos == (station.0 + 1, station.1)
+        });
+        if let Some((station, color)) = station { self.activate_respawn(station, color, audio); }
+        self.update_player_barriers(audio);
+    }
+
+    // a locked door opens for good when the player touches it while holding a key of its color
+    fn try_open_door(&mut self, pos: Idx2, audio: &mut Audio<AssetId>) {
+        let color = self.grid[&pos].door_color().unwrap();
+        let unlocked = if let PlayerEnum::Normal(ref mut player) = self.player {
+            player.inventory_mut().take_key(color).is_some()
+        } else {
+            unreachable!()
+        };
+        if unlocked {
+            audio.play_sound(SoundId::Button);
+            let cell = self.grid.remove(&pos).unwrap();
+            self.collider.remove_hitbox(cell.id());
+            self.effects.push(effect::square_fade(idx_to_vec(pos), self.time()));
+        }
+    }
+
+    // warps of a color lead to the respawn station of that color that the player touched most recently
+    fn activate_respawn(&mut self, station: Idx2, color: WarpColor, audio: &mut Audio<AssetId>) {
+        let respawn = cell::respawn_pos(station);
+        if self.respawns[&color] == respawn { return; }
+        audio.play_sound(SoundId::Button);
+        self.respawns.insert(color, respawn);
+        for &(other, _) in self.respawn_stations.iter().filter(|&&(_, c)| c == color) {
+            for &pos in [other, (other.0 + 1, other.1)].iter() {
+                self.grid.get_mut(&pos).unwrap().set_spawn_active(other == station);
+            }
+        }
+        self.effects.push(effect::color_fade(respawn, self.time(), color));
+    }
+
+    fn add_pickup(&mut self, pickup: Pickup) {
+        let id = self.id_gen.next();
+        let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Pickup), pickup.hitbox());
+        assert!(overlaps.is_empty(), "unexpected overlap with pickup");
+        self.pickups.insert(id, pickup);
     }
 
     fn warp(&mut self, warp_id: HbId, audio: &mut Audio<AssetId>) {
-        audio.play_sound(SoundId::Warp);
-        let color = *self.warps.get(&warp_id).unwrap();
+        let color = self.warps[&warp_id].color;
         if self.collider.get_hitbox(warp_id).vel.value != Vec2::zero() {
             self.collider.remove_hitbox(warp_id);
             self.warps.remove(&warp_id);
         }
-        let start_pos = self.player_pos();
+        self.warp_player(color, audio);
+    }
+
+    fn beam_hits_player(&mut self, beam_id: HbId, audio: &mut Audio<AssetId>) {
+        let color = self.lasors.iter().find(|l| l.beam_id() == Some(beam_id)).unwrap().color();
+        self.warp_player(color, audio);
+    }
+
+    fn warp_player(&mut self, color: WarpColor, audio: &mut Audio<AssetId>) {
         let end_pos = self.respawns[&color];
-        if let PlayerEnum::Normal(ref player) = self.player { self.collider.remove_hitbox(player.id()); } else { unreachable!() }
-        self.player = PlayerEnum::Warping(PlayerWarping::new(start_pos, end_pos, color, self.time()));
+        self.start_warping(end_pos, color, Vec2::zero(), audio);
+    }
+
+    // portals cannot be used again until a moment after the player arrives, so that the player does not
+    // bounce back and forth between a pair
+    fn enter_portal(&mut self, portal_id: HbId, audio: &mut Audio<AssetId>) {
+        if self.time() < self.portal_ready_time { return; }
+        let vel = if let PlayerEnum::Normal(ref player) = self.player { player.vel() } else { unreachable!() };
+        let portal = &self.portals[&portal_id];
+        let exit = self.portals.iter().find(|&(&id, p)| id != portal_id && p.color() == portal.color()).unwrap().1;
+        let (end_pos, color, exit_vel) = (exit.exit_pos(), portal.color(), portal.carry_vel(exit, vel));
+        self.start_warping(end_pos, color, exit_vel, audio);
+        self.portal_ready_time = self.player.transition_time() + portal::COOLDOWN;
+    }
+
+    fn start_warping(&mut self, end_pos: Vec2, color: WarpColor, exit_vel: Vec2, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Warp);
+        let start_pos = self.player_pos();
+        let (abilities, inventory) = if let PlayerEnum::Normal(ref player) = self.player {
+            self.collider.remove_hitbox(player.id());
+            (player.abilities(), player.inventory().clone())
+        } else {
+            unreachable!()
+        };
+        let warping = PlayerWarping::new(start_pos, end_pos, color, self.time(), abilities, inventory);
+        self.player = PlayerEnum::Warping(warping.with_exit_vel(exit_vel));
     }
 
+    // a warp with bounces left reflects off the wall, otherwise it vanishes in a puff
     fn warp_hits_wall(&mut self, warp_id: HbId, wall_id: HbId, card_mask: CardMask) {
-        let warp_shape = self.collider.get_hitbox(warp_id).value;
+        let warp_hitbox = self.collider.get_hitbox(warp_id);
+        let warp_shape = warp_hitbox.value;
         let wall_shape = self.collider.get_hitbox(wall_id).value;
         let normal = warp_shape.masked_normal_from(&wall_shape, card_mask);
         if normal.len() < PieceProfile::padding() {
-            self.warps.remove(&warp_id);
-            self.collider.remove_hitbox(warp_id);
-            let pos = warp_shape.pos - normal.dir() * 0.5 * warp_shape.dims().x;
-            let angle = normal.dir().y.atan2(normal.dir().x) - 0.5 * f64::consts::PI;
-            self.effects.push(effect::puff(pos, self.collider.time(), angle));
+            let warp = self.warps.get_mut(&warp_id).unwrap();
+            if warp.bounces == 0 {
+                self.destroy_warp(warp_id, normal.dir());
+            } else {
+                let vel = warp_hitbox.vel.value;
+                let approach = vel * normal.dir();
+                if approach < 0. {
+                    warp.bounces -= 1;
+                    self.collider.set_hitbox_vel(warp_id, HbVel::moving(vel - normal.dir() * (2. * approach)));
+                }
+            }
         }
     }
 
+    // removes a warp with a puff on the side of the warp facing away from normal
+    fn destroy_warp(&mut self, warp_id: HbId, normal: Vec2) {
+        let warp_shape = self.collider.get_hitbox(warp_id).value;
+        self.warps.remove(&warp_id);
+        self.collider.remove_hitbox(warp_id);
+        let pos = warp_shape.pos - normal * 0.5 * warp_shape.dims().x;
+        let angle = normal.y.atan2(normal.x) - 0.5 * f64::consts::PI;
+        self.effects.push(effect::puff(pos, self.collider.time(), angle));
+    }
+
+    // standing warps never move, so only fired warps can press buttons
+    fn warp_hits_button(&mut self, warp_id: HbId, button_id: HbId, audio: &mut Audio<AssetId>) {
+        let warp_shape = self.collider.get_hitbox(warp_id).value;
+        let button_shape = self.collider.get_hitbox(button_id).value;
+        let normal = warp_shape.normal_from(&button_shape);
+        self.destroy_warp(warp_id, normal.dir());
+        self.press_button(button_id, audio);
+    }
+
+    fn warp_hits_gravity_switch(&mut self, warp_id: HbId, switch_id: HbId, audio: &mut Audio<AssetId>) {
+        let warp_shape = self.collider.get_hitbox(warp_id).value;
+        let switch_shape = self.collider.get_hitbox(switch_id).value;
+        let normal = warp_shape.normal_from(&switch_shape);
+        self.destroy_warp(warp_id, normal.dir());
+        self.flip_gravity(switch_id, audio);
+    }
+
     fn press_button(&mut self, button_id: HbId, audio: &mut Audio<AssetId>) {
         audio.play_sound(SoundId::Button);
-        self.collider.remove_hitbox(button_id);
-        let mut actions = self.buttons.remove(&button_id).unwrap();
-        for pos in actions.unlock_cells.drain(..) { self.remove_cell(pos); }
-        for (pos, kind) in actions.platforms.drain(..) { self.add_platform(pos, kind); }
+        let time = self.time();
+        let button = self.buttons.get_mut(&button_id).unwrap();
+        button.press(time);
+        if button.is_removed() { self.collider.remove_hitbox(button_id); }
+        self.update_logic(audio);
+    }
+
+    fn logic_step(&mut self, audio: &mut Audio<AssetId>) {
+        let time = self.time();
+        for button in self.buttons.values_mut() { button.check_timer(time); }
+        self.update_plates(audio);
+        self.update_logic(audio);
+    }
+
+    // plates are pressed while they overlap a crate or the player, and the logic is updated when any change
+    fn update_plates(&mut self, audio: &mut Audio<AssetId>) {
+        let mut changed = false;
+        for (&id, plate) in self.plates.iter_mut() {
+            let pressed = self.collider.get_overlaps(id).iter().any(|p| p.kind == PieceKind::Crate || p.kind == PieceKind::Player);
+            if pressed != plate.pressed {
+                if pressed { audio.play_sound(SoundId::Button); }
+                plate.pressed = pressed;
+                changed = true;
+            }
+        }
+        if changed { self.update_logic(audio); }
+    }
+
+    fn update_logic(&mut self, audio: &mut Audio<AssetId>) {
+        let time = self.time();
+        let inputs = Inputs {
+            time,
+            star: self.star.is_obtained(time),
+            player_idx: vec_to_idx(self.player_pos()),
+            open_buttons: self.buttons.values().filter(|b| b.open).map(|b| b.index).collect(),
+            pressed_plates: self.plates.values().filter(|p| p.pressed).map(|p| p.index).collect(),
+        };
+        for (target, value) in self.logic.update(&inputs) {
+            match target {
+                Target::Gates(index) => self.set_gates_open(index, value, audio),
+                Target::Platforms(index) => self.set_platforms_running(index, value, audio),
+                Target::Lasors(color) => {
+                    for i in 0..self.lasors.len() {
+                        if self.lasors[i].color() == color {
+                            self.lasors[i].set_enabled(value);
+                            if self.lasors[i].is_beam() { self.update_beam(i, audio); }
+                        }
+                    }
+                },
+                Target::Warps(color) => self.set_standing_warps(color, value, audio),
+                Target::Music => {
+                    if value { audio.loop_music(MusicId::BgMusic); } else { audio.stop_music(); }
+                    self.music_on = value;
+                },
+            }
+        }
+        self.lock_pending_gates(audio);
+    }
+
+    fn set_gates_open(&mut self, index: u32, open: bool, audio: &mut Audio<AssetId>) {
+        let gates = self.groups.get(&index).map(|g| g.gates.clone()).unwrap_or_default();
+        if open {
+            for pos in gates { self.remove_cell(pos, audio); }
+        } else {
+            self.pending_gates.extend(gates);
+        }
+    }
+
+    // platforms are added the first time that they run
+    fn set_platforms_running(&mut self, index: u32, running: bool, audio: &mut Audio<AssetId>) {
+        let time = self.time();
+        if let Some(mut group) = self.groups.remove(&index) {
+            if running && group.platform_ids.is_empty() {
+                for (pos, kind, style) in group.platforms.drain(..) {
+                    group.platform_ids.extend(self.add_platform(pos, kind, style, false));
+                }
+            }
+            for id in &group.platform_ids { self.platforms.get_mut(id).unwrap().set_running(running, time); }
+            self.groups.insert(index, group);
+            self.platform_step(audio);
+        }
+    }
+
+    fn set_standing_warps(&mut self, color: WarpColor, present: bool, audio: &mut Audio<AssetId>) {
+        if present {
+            let warps: Vec<_> = self.standing_warps.iter().cloned().filter(|&(_, c)| c == color).collect();
+            for (pos, color) in warps { self.add_warp(pos, Warp::standing(color), Vec2::zero(), Some(audio)); }
+        } else {
+            let collider = &self.collider;
+            let ids: Vec<_> = self.warps.iter()
+                                        .filter(|&(&id, w)| w.color == color && collider.get_hitbox(id).vel.value == Vec2::zero())
+                                        .map(|(&id, _)| id)
+                                        .collect();
+            for id in ids {
+                let pos = self.hb_pos(id);
+                self.warps.remove(&id);
+                self.collider.remove_hitbox(id);
+                self.effects.push(effect::color_fade(pos, self.time(), color));
+            }
+        }
+    }
+
+    // a gate that is waiting to be locked again is simply taken off the pending list,
+    // and anything that was touching a removed cell no longer has it as a barrier
+    fn remove_cell(&mut self, pos: Idx2, audio: &mut Audio<AssetId>) {
+        self.pending_gates.retain(|&p| p != pos);
+        if let Some(cell) = self.grid.remove(&pos) {
+            let overlaps = self.collider.remove_hitbox(cell.id());
+            self.effects.push(effect::square_fade(idx_to_vec(pos), self.collider.time()));
+            self.update_touching_barriers(overlaps, audio);
+        }
     }
 
-    fn remove_cell(&mut self, pos: Idx2) {
-        let cell = self.grid.remove(&pos).unwrap();
-        let overlaps = self.collider.remove_hitbox(cell.id());
-        assert!(overlaps.is_empty(), "unexpected overlap with removed cell");
-        self.effects.push(effect::square_fade(idx_to_vec(pos), self.collider.time()));
+    // crates are updated first, since the player may be standing on one of them
+    fn update_touching_barriers(&mut self, touching: Vec<PieceProfile>, audio: &mut Audio<AssetId>) {
+        for p in &touching {
+            if p.kind == PieceKind::Crate { self.update_crate_barriers(p.id); }
+        }
+        if touching.iter().any(|p| p.kind == PieceKind::Player) { self.update_player_barriers(audio); }
     }
 
-    fn add_platform(&mut self, pos: Idx2, kind: PlatformKind) {
+    // gates stay pending until their space is clear, so that nothing is ever trapped inside of a wall
+    fn lock_pending_gates(&mut self, audio: &mut Audio<AssetId>) {
+        let mut pending_gates = Vec::new();
+        mem::swap(&mut self.pending_gates, &mut pending_gates);
+        let mut touching = Vec::new();
+        for pos in pending_gates.drain(..) {
+            match self.try_lock_gate(pos) {
+                Some(overlaps) => touching.extend(overlaps),
+                None => self.pending_gates.push(pos),
+            }
+        }
+        self.update_touching_barriers(touching, audio);
+    }
+
+    // returns None if the gate's space is occupied, or else the pieces that the new gate is touching;
+    // chirp blocks that are switched on are locked in the same way as gates
+    fn try_lock_gate(&mut self, pos: Idx2) -> Option<Vec<PieceProfile>> {
+        let shape = Shape::square(CELL_LEN as f64).place(idx_to_vec(pos));
+        let id = self.id_gen.next();
+        let profile = PieceProfile::cell(id, pos, CellKind::Wall);
+        let occupied = self.collider.query_overlaps(&shape, &profile).iter().any(|p| {
+            let depth = self.collider.get_hitbox(p.id).value.masked_normal_from(&shape, CardMask::full()).len();
+            (p.kind != PieceKind::Player && p.kind != PieceKind::Crate) || depth >= PieceProfile::padding()
+        });
+        if occupied { return None; }
+        let overlaps = self.collider.add_hitbox(profile, shape.still());
+        let cell = if self.chirp_blocks.contains(&pos) { Cell::chirp_block(id) } else { Cell::gate(id) };
+        self.grid.insert(pos, cell);
+        self.effects.push(effect::square_fade(idx_to_vec(pos), self.time()));
+        Some(overlaps)
+    }
+
+    fn add_platform(&mut self, pos: Idx2, kind: PlatformKind, style: PlatformStyle, dormant: bool) -> Vec<HbId> {
         let update_time = self.step_queue.peek_specific(Step::Platform);
-        for (platform, hitbox) in Platform::new(kind, pos, self.time(), update_time) {
+        let piece_kind = match style {
+            PlatformStyle::Floor(_) => PieceKind::Platform,
+            PlatformStyle::Block => PieceKind::Block,
+        };
+        Platform::new(kind, style, pos, dormant, self.time(), update_time).drain(..).map(|(platform, hitbox)| {
             let id = self.id_gen.next();
             self.platforms.insert(id, platform);
-            self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Platform), hitbox);
-        }
+            self.collider.add_hitbox(PieceProfile::new(id, piece_kind), hitbox);
+            id
+        }).collect()
     }
 
-    fn add_warp(&mut self, pos: Vec2, color: WarpColor, vel: Vec2, audio: Option<&mut Audio<AssetId>>) {
+    fn add_warp(&mut self, pos: Vec2, warp: Warp, vel: Vec2, audio: Option<&mut Audio<AssetId>>) {
         let id = self.id_gen.next();
-        self.warps.insert(id, color);
+        self.warps.insert(id, warp);
         let hitbox = warp::shape().place(pos).moving(vel);
         let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Warp), hitbox);
         let mut warping = false;
         for overlap in overlaps {
             match overlap.kind {
-                PieceKind::Platform | PieceKind::Floor => {},
+                PieceKind::Platform | PieceKind::Block | PieceKind::Floor | PieceKind::Crate | PieceKind::Button |
+                PieceKind::GravitySwitch => {},
                 PieceKind::Player => warping = true,
                 _ => panic!("unexpected overlap with warp"),
             }
@@ -283,13 +823,57 @@ impl GameBoard {
     }
 
     fn player_step(&mut self, audio: &mut Audio<AssetId>) {
-        if let PlayerEnum::Normal(ref mut player) = self.player { player.step(audio); }
-        self.update_player_hitbox_vel();
+        if self.update_player_gravity() { self.update_player_barriers(audio); }
+        let (dropping, chirping) = if let PlayerEnum::Normal(ref mut player) = self.player {
+            player.step(audio);
+            (player.is_dropping(), player.take_chirp())
+        } else {
+            (false, false)
+        };
+        if chirping { self.chirp(audio); }
+        if dropping {
+            self.update_player_barriers(audio);
+        } else {
+            self.update_player_hitbox_vel();
+        }
+    }
+
+    // the chirp's sound wave toggles chirp blocks, wakes dormant platforms, and stuns lasors within its range
+    fn chirp(&mut self, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Chirp);
+        let (pos, time) = (self.player_pos(), self.time());
+        self.effects.push(effect::chirp_wave(pos, time, CHIRP_RANGE));
+        let in_range = |target: Vec2| (target - pos).len() <= CHIRP_RANGE;
+
+        let blocks: Vec<_> = self.chirp_blocks.iter().cloned().filter(|&idx| in_range(idx_to_vec(idx))).collect();
+        for idx in blocks {
+            if self.grid.contains_key(&idx) || self.pending_gates.contains(&idx) {
+                self.remove_cell(idx, audio);
+            } else {
+                self.pending_gates.push(idx);
+            }
+        }
+
+        let collider = &self.collider;
+        let mut woken = false;
+        for (&id, platform) in self.platforms.iter_mut() {
+            if in_range(collider.get_hitbox(id).value.pos) && platform.wake(time) { woken = true; }
+        }
+        if woken { self.platform_step(audio); }
+
+        for i in 0..self.lasors.len() {
+            if in_range(self.lasors[i].pos()) {
+                self.lasors[i].stun(time + CHIRP_STUN_DURATION);
+                if self.lasors[i].is_beam() { self.update_beam(i, audio); }
+            }
+        }
+        self.lock_pending_gates(audio);
     }
 
-    fn platform_step(&mut self) {
+    fn platform_step(&mut self, audio: &mut Audio<AssetId>) {
         let time = self.time();
         let next_time = self.step_queue.peek_specific(Step::Platform);
+        let mut pushing_block = false;
         for (&id, platform) in self.platforms.iter() {
             let mut hitbox = self.collider.get_hitbox(id);
             hitbox.vel = platform.step(hitbox.value.pos, time, next_time);
@@ -301,31 +885,208 @@ impl GameBoard {
                     let player_shape = self.collider.get_hitbox(player.id()).value;
                     player.update_platform_vel(&player_shape, &hitbox);
                     self.collider.set_hitbox_vel(player.id(), HbVel::moving_until(player.vel(), next_player_step_time));
+                    pushing_block |= platform.is_solid();
                 }
             }
         }
+        if pushing_block { self.update_player_barriers(audio); }
     }
 
-    fn warp_effect_step(&mut self) {
-        let color = match self.player {
-            PlayerEnum::Warping(ref player) => Some(player.color()),
-            _ => None,
+    fn effect_step(&mut self) {
+        let pos = self.player_pos();
+        match self.player {
+            PlayerEnum::Warping(ref player) => {
+                self.effects.push(effect::color_fade(pos, self.collider.time(), player.color()));
+            },
+            PlayerEnum::Normal(ref player) if player.is_dashing() => {
+                let (tex, mirror) = player.tex_and_mirror();
+                self.effects.push(effect::afterimage(pos, self.collider.time(), tex, mirror));
+            },
+            _ => {},
+        }
+    }
+
+    // crates fall under gravity, and are pushed along while the player walks into their side
+    fn crate_step(&mut self, audio: &mut Audio<AssetId>) {
+        let ids: Vec<_> = self.crates.keys().cloned().collect();
+        for &id in &ids {
+            let push_speed = self.crate_push_speed(id);
+            let gravity = self.gravity_at(self.hb_pos(id));
+            let (fall_accel, max_fall_speed) = (self.physics.fall_accel, self.physics.max_fall_speed);
+            self.crates.get_mut(&id).unwrap().body.step(player::STEP_PERIOD, push_speed, gravity, fall_accel, max_fall_speed);
+            self.update_crate_barriers(id);
+        }
+        // crates that start or stop touching the player are already reported by collider events
+        let touching_player = if let PlayerEnum::Normal(ref player) = self.player {
+            ids.iter().any(|&id| self.collider.is_overlapping(id, player.id()))
+        } else {
+            false
         };
-        if let Some(color) = color {
-            let pos = self.player_pos();
-            self.effects.push(effect::color_fade(pos, self.collider.time(), color));
+        if touching_player { self.update_player_barriers(audio); }
+    }
+
+    fn crate_push_speed(&self, crate_id: HbId) -> Option<f64> {
+        if let (PlayerEnum::Normal(ref player), Some(dir)) = (&self.player, self.move_dir) {
+            let player_shape = self.collider.get_hitbox(player.id()).value;
+            let crate_shape = self.collider.get_hitbox(crate_id).value;
+            let mut sides = CardMask::empty();
+            sides[Card::PlusX] = true;
+            sides[Card::MinusX] = true;
+            let normal = player_shape.masked_normal_from(&crate_shape, sides);
+            let overlap_y = 0.5 * (player_shape.dims().y + crate_shape.dims().y) - (player_shape.pos.y - crate_shape.pos.y).abs();
+            let pushing = normal.len() < PieceProfile::padding() && overlap_y > PieceProfile::padding() &&
+                          normal.dir().x * dir.signum() < 0.;
+            if pushing { return Some(dir.signum() * self.physics.crate_push_speed); }
+        }
+        None
+    }
+
+    fn update_crate_barriers(&mut self, crate_id: HbId) {
+        let shape = self.collider.get_hitbox(crate_id).value;
+        let overlaps = self.collider.get_overlaps(crate_id);
+        let (grid, collider) = (&self.grid, &self.collider);
+        let up = self.crates[&crate_id].body.gravity().up();
+        let barriers = overlaps.iter().filter_map(|pr| match pr.kind {
+            PieceKind::Floor | PieceKind::Platform => Some((collider.get_hitbox(pr.id), up.into(), Surface::plain())),
+            PieceKind::Wall if pr.index.is_some() => {
+                let index = pr.index.unwrap();
+                let mask = wall_card_mask(grid, index, false);
+                if mask == CardMask::empty() { None } else { Some((collider.get_hitbox(pr.id), mask, grid[&index].surface())) }
+            },
+            PieceKind::Wall | PieceKind::Block | PieceKind::Crate => {
+                Some((collider.get_hitbox(pr.id), CardMask::full(), Surface::plain()))
+            },
+            _ => None,
+        });
+        let body = &mut self.crates.get_mut(&crate_id).unwrap().body;
+        body.update_barriers(shape, barriers);
+        let next_time = self.step_queue.peek_specific(Step::Crate);
+        self.collider.set_hitbox_vel(crate_id, HbVel::moving_until(body.vel(), next_time));
+    }
+
+    // enemies turn around when there is a wall ahead, and walkers also turn at ledges
+    fn enemy_step(&mut self) {
+        let next_time = self.step_queue.peek_specific(Step::Enemy);
+        let ids: Vec<_> = self.enemies.keys().cloned().collect();
+        for id in ids {
+            let shape = self.collider.get_hitbox(id).value;
+            let enemy = self.enemies[&id];
+            let blocked = self.query_point(enemy.ahead(&shape), PieceKind::Sight);
+            let ledge = enemy.kind() == EnemyKind::Walker &&
+                        !self.query_point(enemy.ground_ahead(&shape), PieceKind::Footing);
+            let enemy = self.enemies.get_mut(&id).unwrap();
+            if blocked || ledge { enemy.turn(); }
+            self.collider.set_hitbox_vel(id, HbVel::moving(enemy.vel(shape.pos, next_time)));
+        }
+    }
+
+    // returns whether there are any pieces at the point that a piece of the given kind would interact with
+    // queries share a single id, since they are never added to the collider
+    fn query_point(&self, pos: Vec2, kind: PieceKind) -> bool {
+        let profile = PieceProfile::new(self.query_id, kind);
+        !self.collider.query_overlaps(&Shape::rect(Vec2::zero()).place(pos), &profile).is_empty()
+    }
+
+    fn lasor_update_step(&mut self, audio: &mut Audio<AssetId>) {
+        let player_pos = self.player_pos();
+        let time = self.time();
+        for i in 0..self.lasors.len() {
+            if self.lasors[i].update_stun(time) && self.lasors[i].is_beam() { self.update_beam(i, audio); }
+            if self.lasors[i].is_aiming() {
+                let sighted = self.has_line_of_sight(self.lasors[i].pos(), player_pos);
+                self.lasors[i].aim(player_pos, sighted, LASOR_UPDATE_PERIOD);
+            } else if self.lasors[i].is_beam() {
+                self.update_beam(i, audio);
+            }
+        }
+        self.steer_warps(player_pos);
+        self.expire_warps();
+    }
+
+    fn steer_warps(&mut self, player_pos: Vec2) {
+        let ids: Vec<_> = self.warps.iter().filter(|&(_, w)| w.flight != Flight::Straight).map(|(&id, _)| id).collect();
+        for id in ids {
+            let hitbox = self.collider.get_hitbox(id);
+            let vel = self.warps[&id].flight.steer(hitbox.vel.value, player_pos - hitbox.value.pos, LASOR_UPDATE_PERIOD);
+            self.collider.set_hitbox_vel(id, HbVel::moving(vel));
+        }
+    }
+
+    fn expire_warps(&mut self) {
+        let time = self.time();
+        let ids: Vec<_> = self.warps.iter().filter(|&(_, w)| w.expire_time <= time).map(|(&id, _)| id).collect();
+        for id in ids {
+            let pos = self.hb_pos(id);
+            let color = self.warps.remove(&id).unwrap().color;
+            self.collider.remove_hitbox(id);
+            self.effects.push(effect::color_fade(pos, time, color));
         }
     }
 
+    // measures the beam up to the first wall or block, and resizes its hitbox to match,
+    // adding or removing the hitbox as the beam is shown or hidden
+    fn update_beam(&mut self, lasor_index: usize, audio: &mut Audio<AssetId>) {
+        let (start, dir) = (self.lasors[lasor_index].beam_start(), self.lasors[lasor_index].beam_dir());
+        let length = self.beam_length(start, dir);
+        let lasor = &mut self.lasors[lasor_index];
+        let unchanged = lasor.beam_length() == length && lasor.beam_id().is_some() == lasor.is_beam_shown();
+        if unchanged { return; }
+        lasor.set_beam_length(length);
+        if let Some(id) = lasor.beam_id() {
+            self.collider.remove_hitbox(id);
+            lasor.set_beam_id(None);
+        }
+        if lasor.is_beam_shown() {
+            let id = self.id_gen.next();
+            lasor.set_beam_id(Some(id));
+            let color = lasor.color();
+            let overlaps = self.collider.add_hitbox(PieceProfile::new(id, PieceKind::Beam), lasor.beam_shape().still());
+            if overlaps.iter().any(|p| p.kind == PieceKind::Player) { self.warp_player(color, audio); }
+        }
+    }
+
+    // beams reach up to the first wall, block, or crate in their way
+    fn beam_length(&self, start: Vec2, dir: Vec2) -> f64 {
+        let max_length = self.room_pixels().x.max(self.room_pixels().y);
+        max_length * self.sight_fraction(start, start + dir * max_length)
+    }
+
+    fn has_line_of_sight(&self, start: Vec2, end: Vec2) -> bool { self.sight_fraction(start, end) == 1. }
+
+    // fraction of the way from start to end that can be seen before a wall, block, or crate is in the way,
+    // checking only the pieces that overlap the bounds of the line
+    fn sight_fraction(&self, start: Vec2, end: Vec2) -> f64 {
+        let bounds = Shape::rect(v2((end.x - start.x).abs(), (end.y - start.y).abs())).place((start + end) * 0.5);
+        let profile = PieceProfile::new(self.query_id, PieceKind::Sight);
+        self.collider.query_overlaps(&bounds, &profile).iter()
+                     .filter_map(|p| segment_entry(start, end, &self.collider.get_hitbox(p.id).value))
+                     .fold(1., f64::min)
+    }
+
+    fn next_lasor_fire_time(&self) -> f64 {
+        self.lasors.iter().map(|l| l.next_fire_time()).fold(f64::INFINITY, f64::min)
+    }
+
+    // fires every lasor that is due, each on its own schedule, and switches beams on or off
     fn lasors_step(&mut self, audio: &mut Audio<AssetId>) {
-        if self.lasors.len() > 0 {
-            audio.play_sound(SoundId::Lasor);
-            let player_pos = self.player_pos();
-            let mut new_warps: Vec<_> = self.lasors.iter().map(|l| l.fire(player_pos)).collect();
-            for (warp_pos, warp_color, warp_vel) in new_warps.drain(..) {
-                self.add_warp(warp_pos, warp_color, warp_vel, Some(audio));
+        let time = self.time();
+        let mut new_warps = Vec::new();
+        for i in 0..self.lasors.len() {
+            if self.lasors[i].next_fire_time() > time { continue; }
+            if self.lasors[i].is_beam() {
+                self.lasors[i].switch_beam();
+                if self.lasors[i].is_beam_shown() { audio.play_sound(SoundId::Lasor); }
+                self.update_beam(i, audio);
+            } else if self.lasors[i].can_fire() {
+                new_warps.push(self.lasors[i].fire());
+            } else {
+                self.lasors[i].skip();
             }
         }
+        if !new_warps.is_empty() { audio.play_sound(SoundId::Lasor); }
+        for (warp_pos, warp, warp_vel) in new_warps.drain(..) {
+            self.add_warp(warp_pos, warp, warp_vel, Some(audio));
+        }
     }
 
     // TODO consider only drawing tiles that are on-screen?
@@ -335,23 +1096,49 @@ impl GameBoard {
         let camera = self.camera_pos(ctx);
         background::draw(renderer, camera, self.room_pixels(), time, ctx.dims().0);
         let renderer = &mut renderer.sprite_mode();
-        for (&pos, cell) in self.grid.iter() { cell.draw(renderer, idx_to_vec(pos) - camera); }
-        for &button_id in self.buttons.keys() {
-            button::draw(renderer, vec_to_affine(self.hb_pos(button_id) - camera));
+        for (&pos, zone) in self.gravity_zones.iter() { zone.draw(renderer, vec_to_affine(idx_to_vec(pos) - camera)); }
+        for (&pos, cell) in self.grid.iter() { cell.draw(renderer, idx_to_vec(pos) - camera, time); }
+        for &pos in self.chirp_blocks.iter().filter(|pos| !self.grid.contains_key(pos)) {
+            cell::draw_chirp_block_outline(renderer, idx_to_vec(pos) - camera);
         }
+        for (&button_id, button) in self.buttons.iter() {
+            if !button.is_removed() { button.draw(renderer, vec_to_affine(self.hb_pos(button_id) - camera), time); }
+        }
+        for (&plate_id, plate) in self.plates.iter() { plate.draw(renderer, vec_to_affine(self.hb_pos(plate_id) - camera)); }
+        for (&crate_id, crate_piece) in self.crates.iter() {
+            crate_piece.draw(renderer, vec_to_affine(self.hb_pos(crate_id) - camera));
+        }
+        for (&pickup_id, pickup) in self.pickups.iter() {
+            pickup.draw(renderer, vec_to_affine(self.hb_pos(pickup_id) - camera), time);
+        }
+        for (&key_id, key) in self.keys.iter() { key.draw(renderer, vec_to_affine(self.hb_pos(key_id) - camera), time); }
         for (&platform_id, platform) in self.platforms.iter() {
             platform.draw(renderer, vec_to_affine(self.hb_pos(platform_id) - camera), time);
         }
-        let next_lasor_fire_time = self.step_queue.peek_specific(Step::LasorFire);
-        for lasor in &self.lasors {
-            lasor.draw(renderer, camera, time, next_lasor_fire_time, player_pos);
+        for spring in self.springs.values() { spring.draw(renderer, camera, time); }
+        let portals_ready = time >= self.portal_ready_time;
+        for (&portal_id, portal) in self.portals.iter() {
+            portal.draw(renderer, vec_to_affine(self.hb_pos(portal_id) - camera), time, portals_ready);
+        }
+        for &switch_id in &self.gravity_switches {
+            gravity::draw_switch(renderer, vec_to_affine(self.hb_pos(switch_id) - camera), self.gravity_flipped);
         }
-        for (&warp_id, &warp_color) in self.warps.iter() {
-            warp_color.draw_warp(renderer, vec_to_affine(self.hb_pos(warp_id) - camera), time);
+        for lasor in &self.lasors { lasor.draw(renderer, camera, time); }
+        for (&enemy_id, enemy) in self.enemies.iter() {
+            enemy.draw(renderer, vec_to_affine(self.hb_pos(enemy_id) - camera), time);
+        }
+        for (&warp_id, warp) in self.warps.iter() {
+            warp.color.draw_warp(renderer, vec_to_affine(self.hb_pos(warp_id) - camera), time);
         }
         self.star.draw(renderer, vec_to_affine(self.hb_pos(self.star.id()) - camera), time);
         self.effects.retain(|e| e.draw(renderer, camera, time));
         self.player.draw(renderer, vec_to_affine(player_pos - camera), time);
+        if let PlayerEnum::Normal(ref player) = self.player {
+            let (_, mirror) = player.tex_and_mirror();
+            player.inventory().draw_trail(renderer, vec_to_affine(player_pos - camera), mirror, time);
+        }
+        for water_cell in &self.water { water_cell.draw(renderer, camera, time); }
+        if let Some(inventory) = self.player.inventory() { inventory.draw_hud(renderer, SCREEN_PIXELS_HEIGHT); }
     }
 
     fn camera_pos(&self, ctx: &AppContext<AssetId>) -> Vec2 {
@@ -369,7 +1156,8 @@ impl GameBoard {
     fn hb_pos(&self, id: HbId) -> Vec2 { self.collider.get_hitbox(id).value.pos }
 }
 
-fn wall_card_mask(grid: &HashMap<Idx2, Cell>, index: Idx2, player_above_wall: bool) -> CardMask {
+// player_on_wall is true if the player is standing on the wall, given the direction of gravity
+fn wall_card_mask(grid: &HashMap<Idx2, Cell>, index: Idx2, player_on_wall: bool) -> CardMask {
     let mut card_mask = CardMask::empty();
     for &card in Card::values().iter() {
         let offset = card_offset(card);
@@ -378,7 +1166,7 @@ fn wall_card_mask(grid: &HashMap<Idx2, Cell>, index: Idx2, player_above_wall: bo
             Some(CellKind::Wall) => false,
             Some(CellKind::Floor) => match card {
                 Card::PlusY | Card::MinusY => true,
-                Card::PlusX | Card::MinusX => !player_above_wall,
+                Card::PlusX | Card::MinusX => !player_on_wall,
             },
             _ => true,
         };
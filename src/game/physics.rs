@@ -0,0 +1,157 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::surface::Material;
+
+// tunable values for player movement, in units of pixels and seconds
+#[derive(Copy, Clone)]
+pub struct PhysicsProfile {
+    pub move_accel: f64,
+    pub stop_accel: f64,
+    pub fall_accel: f64,
+    pub jump_speed: f64,
+    pub max_fall_speed: f64,
+    pub max_move_speed: f64,
+    pub jump_duration: f64,
+    pub jump_run_duration: f64,
+    pub wall_slide_speed: f64,
+    pub wall_jump_speed: f64,
+    pub dash_speed: f64,
+    pub dash_duration: f64,
+    pub coyote_duration: f64,
+    pub jump_buffer_duration: f64,
+    pub ice_friction: f64,
+    pub mud_friction: f64,
+    pub mud_speed_scale: f64,
+    pub spring_speed: f64,
+    pub spring_duration: f64,
+    pub low_gravity_scale: f64,
+    pub water_gravity_scale: f64,
+    pub swim_max_speed: f64,
+    pub swim_stroke_speed: f64,
+    pub crate_push_speed: f64,
+}
+
+impl PhysicsProfile {
+    pub fn standard() -> PhysicsProfile {
+        PhysicsProfile {
+            move_accel: 150.,
+            stop_accel: 350.,
+            fall_accel: 360.,
+            jump_speed: 80.,
+            max_fall_speed: 120.,
+            max_move_speed: 80.,
+            jump_duration: 0.21,
+            jump_run_duration: 0.10,
+            wall_slide_speed: 30.,
+            wall_jump_speed: 70.,
+            dash_speed: 170.,
+            dash_duration: 0.16,
+            coyote_duration: 0.08,
+            jump_buffer_duration: 0.12,
+            ice_friction: 0.15,
+            mud_friction: 2.5,
+            mud_speed_scale: 0.5,
+            spring_speed: 160.,
+            spring_duration: 0.15,
+            low_gravity_scale: 0.4,
+            water_gravity_scale: 0.25,
+            swim_max_speed: 45.,
+            swim_stroke_speed: 55.,
+            crate_push_speed: 30.,
+        }
+    }
+
+    // parses lines of the form "name = value", applying the lines at the top of the file
+    // and then those under the "[level N]" header matching `level_num`, if present
+    pub fn parse(contents: &str, level_num: usize) -> Result<PhysicsProfile, String> {
+        let mut profile = PhysicsProfile::standard();
+        let mut in_section = true;
+        for (line_num, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            } else if line.starts_with('[') && line.ends_with(']') {
+                let header = line[1..line.len() - 1].trim();
+                let level = header.strip_prefix("level").and_then(|n| n.trim().parse::<usize>().ok());
+                let level = level.ok_or_else(|| format!("line {}: invalid section header \"{}\"", line_num + 1, line))?;
+                in_section = level == level_num;
+            } else if in_section {
+                let mut parts = line.splitn(2, '=').map(str::trim);
+                let (name, value) = (parts.next().unwrap(), parts.next());
+                let value = value.and_then(|v| v.parse::<f64>().ok())
+                                 .ok_or_else(|| format!("line {}: expected \"name = number\"", line_num + 1))?;
+                let field = profile.field_mut(name).ok_or_else(|| format!("line {}: unknown value \"{}\"", line_num + 1, name))?;
+                // every speed, acceleration, duration, and scale must be positive, or else movement breaks down
+                if !value.is_finite() || value <= 0. {
+                    return Err(format!("line {}: \"{}\" must be a positive number", line_num + 1, name));
+                }
+                *field = value;
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn held_jump_duration(&self, x_speed: f64) -> f64 {
+        self.jump_duration + self.jump_run_duration * (x_speed / self.max_move_speed)
+    }
+
+    // acceleration used when stopping or turning around on the ground
+    pub fn stop_accel(&self, material: Material) -> f64 {
+        match material {
+            Material::Normal => self.stop_accel,
+            Material::Ice => self.stop_accel * self.ice_friction,
+            Material::Mud => self.stop_accel * self.mud_friction,
+        }
+    }
+
+    pub fn max_move_speed(&self, material: Material) -> f64 {
+        match material {
+            Material::Mud => self.max_move_speed * self.mud_speed_scale,
+            _ => self.max_move_speed,
+        }
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name {
+            "move_accel" => Some(&mut self.move_accel),
+            "stop_accel" => Some(&mut self.stop_accel),
+            "fall_accel" => Some(&mut self.fall_accel),
+            "jump_speed" => Some(&mut self.jump_speed),
+            "max_fall_speed" => Some(&mut self.max_fall_speed),
+            "max_move_speed" => Some(&mut self.max_move_speed),
+            "jump_duration" => Some(&mut self.jump_duration),
+            "jump_run_duration" => Some(&mut self.jump_run_duration),
+            "wall_slide_speed" => Some(&mut self.wall_slide_speed),
+            "wall_jump_speed" => Some(&mut self.wall_jump_speed),
+            "dash_speed" => Some(&mut self.dash_speed),
+            "dash_duration" => Some(&mut self.dash_duration),
+            "coyote_duration" => Some(&mut self.coyote_duration),
+            "jump_buffer_duration" => Some(&mut self.jump_buffer_duration),
+            "ice_friction" => Some(&mut self.ice_friction),
+            "mud_friction" => Some(&mut self.mud_friction),
+            "mud_speed_scale" => Some(&mut self.mud_speed_scale),
+            "spring_speed" => Some(&mut self.spring_speed),
+            "spring_duration" => Some(&mut self.spring_duration),
+            "low_gravity_scale" => Some(&mut self.low_gravity_scale),
+            "water_gravity_scale" => Some(&mut self.water_gravity_scale),
+            "swim_max_speed" => Some(&mut self.swim_max_speed),
+            "swim_stroke_speed" => Some(&mut self.swim_stroke_speed),
+            "crate_push_speed" => Some(&mut self.crate_push_speed),
+            _ => None,
+        }
+    }
+}
@@ -20,7 +20,11 @@ use super::{Idx2, CELL_LEN};
 use super::cell::CellKind;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
-pub enum PieceKind { Wall, Floor, Player, Platform, Star, Button, Warp }
+pub enum PieceKind {
+    Wall, Floor, Player, Platform, Block, Star, Button, Warp, Pickup, Spring, GravitySwitch, Water, Beam, Enemy, Crate, Plate, Key, Portal,
+    // kinds that are only used to query the collider
+    Sight, Footing,
+}
 
 impl From<CellKind> for PieceKind {
     fn from(kind: CellKind) -> PieceKind {
@@ -46,11 +50,25 @@ impl PieceProfile {
     fn can_interact_asym(&self, other: &PieceProfile) -> bool {
         match self.kind {
             PieceKind::Player => match other.kind {
-                PieceKind::Wall | PieceKind::Floor | PieceKind::Platform | PieceKind::Button | PieceKind::Warp | PieceKind::Star => true,
+                PieceKind::Wall | PieceKind::Floor | PieceKind::Platform | PieceKind::Block | PieceKind::Button | PieceKind::Warp |
+                PieceKind::Star | PieceKind::Pickup | PieceKind::Spring | PieceKind::GravitySwitch |
+                PieceKind::Water | PieceKind::Beam | PieceKind::Enemy | PieceKind::Crate | PieceKind::Plate |
+                PieceKind::Key | PieceKind::Portal => true,
                 _ => false,
             },
+            PieceKind::Crate => {
+                [PieceKind::Wall, PieceKind::Floor, PieceKind::Platform, PieceKind::Block, PieceKind::Crate, PieceKind::Plate]
+                    .contains(&other.kind)
+            },
+            // used to query whether a line of sight is blocked
+            PieceKind::Sight => [PieceKind::Wall, PieceKind::Block, PieceKind::Crate].contains(&other.kind),
+            // used to query whether there is ground for an enemy to walk on
+            PieceKind::Footing => {
+                [PieceKind::Wall, PieceKind::Floor, PieceKind::Platform, PieceKind::Block, PieceKind::Crate].contains(&other.kind)
+            },
             PieceKind::Warp => match other.kind {
-                PieceKind::Floor | PieceKind::Platform => true,
+                PieceKind::Floor | PieceKind::Platform | PieceKind::Block | PieceKind::Crate | PieceKind::Button |
+                PieceKind::GravitySwitch => true,
                 PieceKind::Wall => other.index.is_some(),
                 _ => false,
             },
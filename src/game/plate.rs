@@ -0,0 +1,44 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::geom::{Shape, PlacedShape, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::{CELL_LEN, Idx2};
+use super::util::idx_to_vec;
+
+const WIDTH: f64 = CELL_LEN as f64 - 0.1;
+const HEIGHT: f64 = 1.;
+const Y_OFFSET: f64 = -0.5 * CELL_LEN as f64 + 0.5 * HEIGHT;
+
+// a pressure plate is pressed only while a crate or the player rests on it,
+// and its index is its signal in the level's logic
+pub struct Plate { pub index: u32, pub pressed: bool }
+
+impl Plate {
+    pub fn new(index: u32) -> Plate { Plate { index, pressed: false } }
+
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine) {
+        let tile = if self.pressed { SpriteId::TileR5C3 } else { SpriteId::TileR5C2 };
+        renderer.draw(&affine.pre_translate(0., -Y_OFFSET), tile);
+    }
+}
+
+pub fn shape(pos: Idx2) -> PlacedShape {
+    Shape::rect(v2(WIDTH, HEIGHT)).place(idx_to_vec(pos) + v2(0., Y_OFFSET))
+}
@@ -23,6 +23,7 @@ use collider::geom::{Vec2, v2, Shape};
 
 use crate::asset_id::{AssetId, SpriteId};
 use super::util::idx_to_vec;
+use super::surface::{Material, Surface};
 use super::Idx2;
 
 #[derive(Copy, Clone)]
@@ -56,20 +57,74 @@ impl PlatformKind {
     }
 }
 
-pub struct Platform { kind: PlatformKind, index: u32, center: Vec2, fade_in_time: f64 }
+// blocks collide on all sides, while floors are one-way platforms topped with some material
+#[derive(Copy, Clone)]
+pub enum PlatformStyle { Floor(Material), Block }
+
+// stopped platforms hold their position, and resume their path where they left off;
+// dormant platforms start out stopped until the player chirps near them
+pub struct Platform {
+    kind: PlatformKind,
+    index: u32,
+    center: Vec2,
+    fade_in_time: f64,
+    style: PlatformStyle,
+    stop_time: Option<f64>,
+    time_offset: f64,
+    dormant: bool,
+}
 
 impl Platform {
-    pub fn new(kind: PlatformKind, pos: Idx2, time: f64, end_time: f64) -> Vec<(Platform, Hitbox)> {
+    pub fn new(kind: PlatformKind, style: PlatformStyle, pos: Idx2, dormant: bool, time: f64, end_time: f64) -> Vec<(Platform, Hitbox)> {
         let fade_in_time = if time == 0. { f64::NEG_INFINITY } else { time };
         let center = idx_to_vec(pos);
+        let shape = match style {
+            PlatformStyle::Floor(_) => Shape::rect(v2(24., 8.)),
+            PlatformStyle::Block => Shape::square(16.),
+        };
         (0..kind.count()).map(|index| {
-            let platform = Platform { kind, index, fade_in_time, center };
+            let stop_time = if dormant { Some(time) } else { None };
+            let platform = Platform { kind, index, fade_in_time, center, style, stop_time, time_offset: 0., dormant };
             let pos = platform.position_at_time(time);
             let vel = platform.step(pos, time, end_time);
-            (platform, Hitbox::new(Shape::rect(v2(24., 8.)).place(pos), vel))
+            (platform, Hitbox::new(shape.place(pos), vel))
         }).collect()
     }
 
+    pub fn is_solid(&self) -> bool {
+        match self.style {
+            PlatformStyle::Floor(_) => false,
+            PlatformStyle::Block => true,
+        }
+    }
+
+    // the platform's own velocity is not included, since it is already the velocity of its hitbox
+    pub fn surface(&self) -> Surface {
+        match self.style {
+            PlatformStyle::Floor(material) => Surface::one_way(material),
+            PlatformStyle::Block => Surface::plain(),
+        }
+    }
+
+    // returns true if the platform was dormant
+    pub fn wake(&mut self, time: f64) -> bool {
+        if !self.dormant { return false; }
+        self.dormant = false;
+        self.set_running(true, time);
+        true
+    }
+
+    pub fn set_running(&mut self, running: bool, time: f64) {
+        match (self.stop_time, running) {
+            (None, false) => self.stop_time = Some(time),
+            (Some(stop_time), true) => {
+                self.time_offset += time - stop_time;
+                self.stop_time = None;
+            },
+            _ => {},
+        }
+    }
+
     pub fn step(&self, pos: Vec2, time: f64, end_time: f64) -> HbVel {
         let delta_time = end_time - time;
         let vel = if delta_time > 0.01 {
@@ -81,11 +136,20 @@ impl Platform {
         HbVel::moving_until(vel, end_time)
     }
 
-    fn position_at_time(&self, time: f64) -> Vec2 { self.center + self.kind.offset(self.index, time) }
+    fn position_at_time(&self, time: f64) -> Vec2 {
+        let time = self.stop_time.unwrap_or(time) - self.time_offset;
+        self.center + self.kind.offset(self.index, time)
+    }
 
     pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
         let time = time - self.fade_in_time;
         let flash_ratio = 1.0 - time;
-        renderer.draw_flash(&affine, SpriteId::Platform, flash_ratio);
+        let tex = match self.style {
+            PlatformStyle::Floor(Material::Normal) => SpriteId::Platform,
+            PlatformStyle::Floor(Material::Ice) => SpriteId::PlatformIce,
+            PlatformStyle::Floor(Material::Mud) => SpriteId::PlatformMud,
+            PlatformStyle::Block => SpriteId::Block,
+        };
+        renderer.draw_flash(&affine, tex, flash_ratio);
     }
 }
@@ -14,6 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::mem;
+
 use collider::geom::{Card, CardMask, Vec2, PlacedShape, Shape, v2};
 use collider::{Hitbox, HbProfile, HbId};
 
@@ -21,54 +23,108 @@ use gate::Audio;
 use gate::renderer::{SpriteRenderer, Affine};
 
 use super::PieceProfile;
+use super::ability::{Ability, Abilities};
+use super::key::Inventory;
+use super::physics::PhysicsProfile;
+use super::gravity::Gravity;
+use super::surface::{Material, Surface};
 use super::util::nearest_card;
 use crate::game_input::HorizDir;
 use crate::asset_id::{AssetId, SpriteId, SoundId};
 
 pub const STEP_PERIOD: f64 = 1. / 60.;
 
-const MOVE_ACCEL: f64 = 150. * STEP_PERIOD;
-const STOP_ACCEL: f64 = 350. * STEP_PERIOD;
-const FALL_ACCEL: f64 = 360. * STEP_PERIOD;
-const JUMP_SPEED: f64 = 80.;
-const MAX_FALL_SPEED: f64 = 120.;
-const MAX_MOVE_SPEED: f64 = 80.;
-fn jump_duration(x_speed: f64) -> f64 { 0.21 + 0.10 * (x_speed / MAX_MOVE_SPEED) }
+const DROP_DURATION: f64 = 0.3;
+const WALL_JUMP_LOCK_DURATION: f64 = 0.15;
 
 const GRAPHIC_STEP_DURATION: f64 = 0.16;
 const GRAPHIC_CHIRP_DELAY: f64 = 0.8;
 const GRAPHIC_CHIRP_DURATION: f64 = 0.125;
+const GRAPHIC_WALL_SLIDE_DURATION: f64 = 0.08;
+const CHIRP_DURATION: f64 = 0.25;
+const CHIRP_COOLDOWN: f64 = 0.5;
 
+// player movement is computed in coordinates where gravity points toward -y, so that the
+// velocities, barriers, and cards stored here are flipped from world coordinates when gravity is flipped
 pub struct Player {
     id: HbId,
+    physics: PhysicsProfile,
+    gravity: Gravity,
     time: f64,
     dir: HorizDir,
     moving: bool,
     state_start_time: f64,
     on_ground: bool,
+    swimming: bool,
     jump_held: bool,
+    jump_key_down: bool,
+    down_key_down: bool,
     queued_jump: bool,
     jump_transition_time: f64,
+    coyote_end_time: f64,
+    jump_buffer_end_time: f64,
+    queued_drop: bool,
+    drop_end_time: f64,
+    wall_jump_dir: HorizDir,
+    wall_jump_end_time: f64,
+    launch_end_time: f64,
+    abilities: Abilities,
+    inventory: Inventory,
+    air_jump_used: bool,
+    queued_dash: bool,
+    dash_used: bool,
+    dash_end_time: f64,
+    dash_vel: Vec2,
+    queued_chirp: bool,
+    chirp_end_time: f64,
     blocked_cards: CardMask,
+    barrier_vels: [Vec2; 4],
     vel: Vec2,
     floor_vel: Vec2,
+    floor_material: Material,
+    floor_one_way: bool,
 }
 
 impl Player {
-    pub fn new(id: HbId, pos: Vec2, time: f64, move_dir: Option<HorizDir>) -> (Player, PlacedShape) {
+    pub fn new(id: HbId, pos: Vec2, time: f64, move_dir: Option<HorizDir>, abilities: Abilities, inventory: Inventory,
+               physics: PhysicsProfile) -> (Player, PlacedShape) {
         let mut player = Player {
             id,
+            physics,
+            gravity: Gravity::normal(),
             time,
             dir: HorizDir::Right,
             moving: false,
             state_start_time: time,
             on_ground: false,
+            swimming: false,
             jump_held: false,
+            jump_key_down: false,
+            down_key_down: false,
             queued_jump: false,
             jump_transition_time: time,
+            coyote_end_time: time,
+            jump_buffer_end_time: time,
+            queued_drop: false,
+            drop_end_time: time,
+            wall_jump_dir: HorizDir::Right,
+            wall_jump_end_time: time,
+            launch_end_time: time,
+            abilities,
+            inventory,
+            air_jump_used: false,
+            queued_dash: false,
+            dash_used: false,
+            dash_end_time: time,
+            dash_vel: Vec2::zero(),
+            queued_chirp: false,
+            chirp_end_time: f64::NEG_INFINITY,
             blocked_cards: CardMask::empty(),
+            barrier_vels: [Vec2::zero(); 4],
             vel: Vec2::zero(),
             floor_vel: Vec2::zero(),
+            floor_material: Material::Normal,
+            floor_one_way: false,
         };
         player.set_movement(move_dir);
         (player, Shape::rect(v2(3.5, 11.)).place(pos))
@@ -76,12 +132,49 @@ impl Player {
 
     pub fn set_time(&mut self, time: f64) { self.time = time; }
     pub fn id(&self) -> HbId { self.id }
-    pub fn vel(&self) -> Vec2 { self.vel }
+    pub fn vel(&self) -> Vec2 { self.gravity.orient(self.vel) }
+    pub fn set_vel(&mut self, vel: Vec2) { self.vel = self.gravity.orient(vel); }
+    pub fn gravity(&self) -> Gravity { self.gravity }
+    pub fn abilities(&self) -> Abilities { self.abilities }
+    pub fn grant_ability(&mut self, ability: Ability) { self.abilities.grant(ability); }
+    pub fn inventory(&self) -> &Inventory { &self.inventory }
+    pub fn inventory_mut(&mut self) -> &mut Inventory { &mut self.inventory }
+    pub fn set_physics(&mut self, physics: PhysicsProfile) { self.physics = physics; }
+
+    // barriers should be updated after changing the direction of gravity
+    pub fn set_gravity(&mut self, gravity: Gravity) {
+        if gravity.flipped != self.gravity.flipped {
+            self.vel.y = -self.vel.y;
+            self.floor_vel = Vec2::zero();
+            self.state_start_time = self.time;
+            self.coyote_end_time = self.time;
+            self.queued_jump = false;
+            self.queued_drop = false;
+            self.jump_held = false;
+            self.on_ground = false;
+        }
+        self.gravity = gravity;
+    }
+
+    pub fn is_swimming(&self) -> bool { self.swimming }
+
+    // swimming up out of the water gives the player a boost so that they can climb out onto land
+    pub fn set_swimming(&mut self, swimming: bool) {
+        if swimming {
+            self.air_jump_used = false;
+            self.dash_used = false;
+            self.jump_held = false;
+        } else if self.vel.y > 0. {
+            self.vel.y = self.vel.y.max(self.physics.jump_speed);
+        }
+        self.swimming = swimming;
+        self.bound_vel();
+    }
 
     pub fn update_platform_vel(&mut self, player_shape: &PlacedShape, platform_hb: &Hitbox) {
-        let normal = player_shape.masked_normal_from(&platform_hb.value, Card::PlusY.into());
+        let normal = player_shape.masked_normal_from(&platform_hb.value, self.gravity.up().into());
         if normal.len() < PieceProfile::padding() && self.on_ground {
-            let vel = platform_hb.vel.value;
+            let vel = self.gravity.orient(platform_hb.vel.value);
             let rel_vel_x = self.vel.x - self.floor_vel.x;
             self.floor_vel = vel;
             self.vel.y = vel.y;
@@ -97,19 +190,78 @@ impl Player {
     }
 
     pub fn press_jump(&mut self) {
-        if self.on_ground && !self.queued_jump {
+        self.jump_key_down = true;
+        if self.can_jump() && !self.queued_jump {
             self.queued_jump = true;
             self.jump_held = true;
+        } else if !self.on_ground {
+            self.jump_buffer_end_time = self.time + self.physics.jump_buffer_duration;
         }
     }
 
-    pub fn release_jump(&mut self) { self.jump_held = false; }
+    pub fn release_jump(&mut self) {
+        self.jump_key_down = false;
+        self.jump_held = false;
+    }
+
+    pub fn press_dash(&mut self) {
+        if !self.on_ground && !self.dash_used && self.abilities.has(Ability::AirDash) { self.queued_dash = true; }
+    }
+
+    // the player may chirp again once the previous chirp has finished and cooled down
+    pub fn press_chirp(&mut self) {
+        if self.time >= self.chirp_end_time + CHIRP_COOLDOWN {
+            self.queued_chirp = true;
+            self.chirp_end_time = self.time + CHIRP_DURATION;
+        }
+    }
+
+    // returns true once for each chirp, so that the board can send out its sound wave
+    pub fn take_chirp(&mut self) -> bool { mem::replace(&mut self.queued_chirp, false) }
+
+    // the player can only drop through one-way floors and platforms
+    pub fn press_down(&mut self) {
+        self.down_key_down = true;
+        if self.on_ground && self.floor_one_way { self.queued_drop = true; }
+    }
+
+    pub fn release_down(&mut self) { self.down_key_down = false; }
+
+    // while dropping, the player falls through one-way floors and platforms
+    pub fn is_dropping(&self) -> bool { self.time < self.drop_end_time }
+
+    // direction of the wall that the player is pushing against while airborne, if any
+    fn wall_dir(&self) -> Option<HorizDir> {
+        let card = if self.dir == HorizDir::Left { Card::MinusX } else { Card::PlusX };
+        if !self.on_ground && self.moving && self.blocked_cards[card] { Some(self.dir) } else { None }
+    }
+
+    fn can_air_jump(&self) -> bool { !self.air_jump_used && self.abilities.has(Ability::DoubleJump) }
+
+    // the player may still jump from the ground shortly after walking off a ledge
+    fn can_ground_jump(&self) -> bool { self.on_ground || self.time < self.coyote_end_time }
+
+    fn can_jump(&self) -> bool { self.swimming || self.can_ground_jump() || self.wall_dir().is_some() || self.can_air_jump() }
+
+    pub fn is_dashing(&self) -> bool { self.time < self.dash_end_time }
+
+    fn is_wall_sliding(&self) -> bool { self.wall_dir().is_some() && self.vel.y < 0. }
+
+    // movement input is ignored for a short time after a wall jump so the player is launched away from the wall
+    fn is_wall_jumping(&self) -> bool { self.time < self.wall_jump_end_time }
+
+    // movement input is also ignored while being launched sideways by a spring
+    fn is_launched(&self) -> bool { self.time < self.launch_end_time }
 
     pub fn step(&mut self, audio: &mut Audio<AssetId>) {
-        let stop_accel = if self.on_ground { STOP_ACCEL } else { MOVE_ACCEL };
+        if self.queued_dash { self.dash(audio); }
+        let move_accel = self.physics.move_accel * STEP_PERIOD;
+        let stop_accel = if self.on_ground { self.physics.stop_accel(self.floor_material) * STEP_PERIOD } else { move_accel };
         let rel_vel_x = self.vel.x - self.floor_vel.x;
-        let rel_vel_x = if self.moving {
-            let accel = if self.dir.signum() == rel_vel_x.signum() { MOVE_ACCEL } else { stop_accel };
+        let rel_vel_x = if self.is_wall_jumping() || self.is_dashing() || self.is_launched() {
+            rel_vel_x
+        } else if self.moving {
+            let accel = if self.dir.signum() == rel_vel_x.signum() { move_accel } else { stop_accel };
             rel_vel_x + self.dir.signum() * accel
         } else if self.on_ground {
             if rel_vel_x.abs() > stop_accel { rel_vel_x - rel_vel_x.signum() * stop_accel } else { 0.0 }
@@ -117,63 +269,197 @@ impl Player {
             rel_vel_x
         };
         self.vel.x = self.floor_vel.x + rel_vel_x;
+        if self.queued_drop {
+            self.queued_drop = false;
+            self.drop_end_time = self.time + DROP_DURATION;
+        }
+        if self.queued_jump && !self.can_jump() {
+            self.queued_jump = false;
+        }
         if self.queued_jump {
             self.jump(audio);
+        } else if self.is_dashing() {
+            self.vel.y = self.dash_vel.y;
         } else if self.time > self.jump_transition_time || !self.jump_held {
             self.jump_held = false;
-            self.vel.y -= FALL_ACCEL;
+            let water_scale = if self.swimming { self.physics.water_gravity_scale } else { 1. };
+            self.vel.y -= self.physics.fall_accel * self.gravity.scale * water_scale * STEP_PERIOD;
         }
         self.bound_vel();
         self.update_on_ground(false);
     }
 
     fn jump(&mut self, audio: &mut Audio<AssetId>) {
-        audio.play_sound(SoundId::Jump);
+        self.queued_jump = false;
+        self.state_start_time = self.time;
+        self.dash_end_time = self.time;
+        let ground_jump = self.can_ground_jump();
+        self.coyote_end_time = self.time;
+        self.jump_buffer_end_time = self.time;
+        let wall_dir = if ground_jump || self.swimming { None } else { self.wall_dir() };
+        audio.play_sound(if wall_dir.is_some() { SoundId::WallJump } else { SoundId::Jump });
+        if self.swimming {
+            self.jump_held = false;
+            self.jump_transition_time = self.time;
+            self.vel.y = self.physics.swim_stroke_speed;
+        } else if let Some(wall_dir) = wall_dir {
+            self.wall_jump_dir = wall_dir.flip();
+            self.wall_jump_end_time = self.time + WALL_JUMP_LOCK_DURATION;
+            self.jump_transition_time = self.time + self.physics.held_jump_duration(self.physics.wall_jump_speed);
+            self.vel = v2(self.wall_jump_dir.signum() * self.physics.wall_jump_speed, self.physics.jump_speed);
+            self.air_jump_used = false;
+            self.dash_used = false;
+        } else if !ground_jump {
+            self.air_jump_used = true;
+            self.jump_transition_time = self.time + self.physics.held_jump_duration(self.vel.x.abs());
+            self.vel.y = self.physics.jump_speed;
+        } else {
+            self.jump_transition_time = self.time + self.physics.held_jump_duration((self.vel.x - self.floor_vel.x).abs());
+            self.vel.y = self.physics.jump_speed + self.floor_vel.y.max(0.0);
+            self.vel.x = self.vel.x - self.floor_vel.x;
+        }
         self.on_ground = false;
+        self.floor_vel = Vec2::zero();
+    }
+
+    // upward launches are held like a jump, so releasing the jump key cuts them short
+    pub fn launch(&mut self, card: Card) {
+        let card = self.gravity.orient_card(card);
         self.queued_jump = false;
         self.state_start_time = self.time;
-        self.jump_transition_time = self.time + jump_duration((self.vel.x - self.floor_vel.x).abs());
-        self.vel.y = JUMP_SPEED + self.floor_vel.y.max(0.0);
-        self.vel.x = self.vel.x - self.floor_vel.x;
+        self.dash_end_time = self.time;
+        self.coyote_end_time = self.time;
+        self.jump_buffer_end_time = self.time;
+        self.air_jump_used = false;
+        self.dash_used = false;
+        self.jump_held = false;
+        let speed = self.physics.spring_speed;
+        match card {
+            Card::PlusY => {
+                self.jump_held = self.jump_key_down;
+                self.jump_transition_time = self.time + self.physics.spring_duration;
+                self.vel.y = speed;
+            },
+            Card::MinusY => self.vel.y = -speed,
+            Card::PlusX | Card::MinusX => {
+                self.launch_end_time = self.time + self.physics.spring_duration;
+                let dir = if card == Card::PlusX { 1. } else { -1. };
+                self.vel = v2(dir * speed, self.vel.y.max(0.));
+            },
+        }
+        self.on_ground = false;
         self.floor_vel = Vec2::zero();
+        self.bound_vel();
     }
 
-    pub fn update_barriers<I: Iterator<Item=(Hitbox, CardMask)>>(&mut self, shape: PlacedShape, near_ground: bool, barriers: I) {
-        let mut barrier_count = 0;
+    // the player stomps on an enemy when falling onto it from above, relative to the player's gravity
+    pub fn is_stomping(&self, rel_enemy_pos: Vec2) -> bool {
+        self.vel.y < 0. && self.gravity.orient(rel_enemy_pos).y < 0.
+    }
+
+    // bouncing off a stomped enemy is held like a jump
+    pub fn stomp(&mut self, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Jump);
+        self.queued_jump = false;
+        self.state_start_time = self.time;
+        self.dash_end_time = self.time;
+        self.air_jump_used = false;
+        self.dash_used = false;
+        self.jump_held = self.jump_key_down;
+        self.jump_transition_time = self.time + self.physics.held_jump_duration(self.vel.x.abs());
+        self.vel.y = self.physics.jump_speed;
+        self.on_ground = false;
+    }
+
+    // the player dashes in the held direction, including up, down, and diagonals,
+    // or straight ahead if no direction is held
+    fn dash(&mut self, audio: &mut Audio<AssetId>) {
+        audio.play_sound(SoundId::Dash);
+        self.queued_dash = false;
+        self.dash_used = true;
+        self.jump_held = false;
+        self.state_start_time = self.time;
+        self.dash_end_time = self.time + self.physics.dash_duration;
+        let x = if self.moving { self.dir.signum() } else { 0. };
+        let y = if self.jump_key_down { 1. } else if self.down_key_down { -1. } else { 0. };
+        let dir = if x == 0. && y == 0. { v2(self.dir.signum(), 0.) } else { v2(x, y).normalize().unwrap() };
+        self.dash_vel = dir * self.physics.dash_speed;
+        self.vel = self.dash_vel;
+    }
+
+    // barriers are given along with the surface that affects the player when stood on
+    pub fn update_barriers<I: Iterator<Item=(Hitbox, CardMask, Surface)>>(&mut self, shape: PlacedShape, near_ground: bool, barriers: I) {
         self.blocked_cards = CardMask::empty();
+        self.barrier_vels = [Vec2::zero(); 4];
+        self.floor_material = Material::Normal;
+        let (mut one_way_floor, mut solid_floor) = (false, false);
         let mut floor_vel = None;
-        for (hitbox, mask) in barriers {
+        for (hitbox, mask, surface) in barriers {
             let normal = shape.masked_normal_from(&hitbox.value, mask);
             if normal.len() < PieceProfile::padding() {
                 if floor_vel.is_none() {
                     floor_vel = Some(Vec2::zero());
                 }
-                let dir = nearest_card(normal.dir()).flip();
+                let dir = self.gravity.orient_card(nearest_card(normal.dir()).flip());
+                let hitbox_vel = self.gravity.orient(hitbox.vel.value);
                 self.blocked_cards[dir] = true;
-                if hitbox.vel.value != Vec2::zero() {
-                    assert!(dir == Card::MinusY, "only floors can be moving barriers");
-                    floor_vel = Some(hitbox.vel.value);
+                if hitbox_vel != Vec2::zero() {
+                    self.barrier_vels[dir as usize] = hitbox_vel;
+                }
+                if dir == Card::MinusY {
+                    self.floor_material = surface.material;
+                    if surface.one_way { one_way_floor = true; } else { solid_floor = true; }
+                    if hitbox_vel != Vec2::zero() || surface.vel != Vec2::zero() {
+                        floor_vel = Some(hitbox_vel + surface.vel);
+                    }
                 }
-                barrier_count += 1;
             }
         }
         if let Some(floor_vel) = floor_vel {
             self.floor_vel = floor_vel;
         }
-        assert!(self.floor_vel == Vec2::zero() || barrier_count <= 1,
-                "cannot touch multiple barriers with moving floor");
+        self.floor_one_way = one_way_floor && !solid_floor;
         self.bound_vel();
         self.update_on_ground(near_ground);
     }
 
+    // true if barriers on opposite sides of the player are closing in on each other
+    pub fn is_crushed(&self) -> bool {
+        let closing = |low: Card, high: Card, vel: fn(Vec2) -> f64| {
+            self.blocked_cards[low] && self.blocked_cards[high] && vel(self.barrier_vel(low)) > vel(self.barrier_vel(high))
+        };
+        closing(Card::MinusX, Card::PlusX, |v| v.x) || closing(Card::MinusY, Card::PlusY, |v| v.y)
+    }
+
+    fn barrier_vel(&self, card: Card) -> Vec2 { self.barrier_vels[card as usize] }
+
     fn bound_vel(&mut self) {
-        self.vel.x = self.vel.x.max(-MAX_MOVE_SPEED + self.floor_vel.x);
-        if self.blocked_cards[Card::MinusX] { self.vel.x = self.vel.x.max(0.0); }
-        self.vel.x = self.vel.x.min(MAX_MOVE_SPEED + self.floor_vel.x);
-        if self.blocked_cards[Card::PlusX] { self.vel.x = self.vel.x.min(0.0); }
-        self.vel.y = self.vel.y.max(-MAX_FALL_SPEED);
+        let max_move_speed = if self.is_dashing() {
+            self.physics.dash_speed
+        } else if self.is_launched() {
+            self.physics.spring_speed
+        } else if self.swimming {
+            self.physics.swim_max_speed
+        } else if self.on_ground {
+            self.physics.max_move_speed(self.floor_material)
+        } else {
+            self.physics.max_move_speed
+        };
+        self.vel.x = self.vel.x.max(-max_move_speed + self.floor_vel.x).min(max_move_speed + self.floor_vel.x);
+        if self.blocked_cards[Card::MinusX] { self.vel.x = self.vel.x.max(self.barrier_vel(Card::MinusX).x); }
+        if self.blocked_cards[Card::PlusX] { self.vel.x = self.vel.x.min(self.barrier_vel(Card::PlusX).x); }
+        let max_fall_speed = if self.is_dashing() {
+            self.physics.dash_speed
+        } else if self.swimming {
+            self.physics.swim_max_speed
+        } else if self.wall_dir().is_some() {
+            self.physics.wall_slide_speed
+        } else {
+            self.physics.max_fall_speed
+        };
+        self.vel.y = self.vel.y.max(-max_fall_speed);
         if self.blocked_cards[Card::PlusY] {
-            self.vel.y = self.vel.y.min(0.0);
+            self.vel.y = self.vel.y.min(self.barrier_vel(Card::PlusY).y);
             self.jump_held = false;
         }
         if self.blocked_cards[Card::MinusY] { self.vel.y = self.vel.y.max(self.floor_vel.y); }
@@ -184,30 +470,49 @@ impl Player {
         match (self.on_ground, on_ground, near_ground) {
             (false, true, _) => {
                 self.state_start_time = self.time - GRAPHIC_STEP_DURATION; // lands with legs together
+                self.wall_jump_end_time = self.time;
+                self.launch_end_time = self.time;
+                self.dash_end_time = self.time;
+                self.air_jump_used = false;
+                self.dash_used = false;
+                self.queued_dash = false;
                 self.queued_jump = false;
                 self.jump_held = false;
                 self.on_ground = true;
+                if self.time < self.jump_buffer_end_time {
+                    self.queued_jump = true;
+                    self.jump_held = self.jump_key_down;
+                }
                 self.bound_vel();
             },
             (true, false, false) => {
+                self.coyote_end_time = if self.is_dropping() { self.time } else { self.time + self.physics.coyote_duration };
                 self.floor_vel = Vec2::zero();
                 self.state_start_time = self.time;
                 self.queued_jump = false;
+                self.queued_drop = false;
                 self.jump_held = false;
                 self.on_ground = false;
                 self.bound_vel();
             },
             (true, false, true) => {
-                self.vel.y = -MAX_FALL_SPEED;
-                self.floor_vel.y = -MAX_FALL_SPEED;
+                self.vel.y = -self.physics.max_fall_speed;
+                self.floor_vel.y = -self.physics.max_fall_speed;
             },
             _ => {},
         }
     }
 
     pub fn tex_and_mirror(&self) -> (SpriteId, bool) {
+        if self.is_wall_jumping() {
+            return (SpriteId::PlayerWallJump, self.wall_jump_dir == HorizDir::Left);
+        }
+        if self.time < self.chirp_end_time { return (SpriteId::PlayerChirp, self.dir == HorizDir::Left); }
         let time = self.time - self.state_start_time;
-        let tex = if !self.on_ground {
+        let tex = if self.is_wall_sliding() {
+            let time = time % (2. * GRAPHIC_WALL_SLIDE_DURATION);
+            if time < GRAPHIC_WALL_SLIDE_DURATION { SpriteId::PlayerWallSlide } else { SpriteId::PlayerWallSlide2 }
+        } else if !self.on_ground {
             SpriteId::PlayerRun
         } else if self.moving {
             let time = time % (2. * GRAPHIC_STEP_DURATION);
@@ -221,7 +526,112 @@ impl Player {
 
     pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine) {
         let (tex, mirror) = self.tex_and_mirror();
-        let affine = if mirror { affine.pre_scale_axes(-1., 1.) } else { affine };
+        let affine = affine.pre_scale_axes(if mirror { -1. } else { 1. }, if self.gravity.flipped { -1. } else { 1. });
         renderer.draw(&affine, tex);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use collider::geom::{CardMask, Shape, v2};
+
+    use super::Player;
+    use crate::game::ability::Abilities;
+    use crate::game::key::Inventory;
+    use crate::game::physics::PhysicsProfile;
+    use crate::game::surface::{Material, Surface};
+
+    fn new_player() -> Player {
+        Player::new(0, v2(0., 0.), 0., None, Abilities::none(), Inventory::empty(), PhysicsProfile::standard()).0
+    }
+
+    // the player is standing on a floor while `on_floor` is true, and falling otherwise
+    fn touch_floor(player: &mut Player, time: f64, on_floor: bool) {
+        touch_surface(player, time, if on_floor { Some(Surface::plain()) } else { None });
+    }
+
+    fn touch_surface(player: &mut Player, time: f64, surface: Option<Surface>) {
+        player.set_time(time);
+        let shape = Shape::rect(v2(3.5, 11.)).place(v2(0., 0.));
+        let floor = Shape::rect(v2(16., 8.)).place(v2(0., -9.5)).still();
+        let barriers = surface.map(|surface| (floor, CardMask::full(), surface));
+        player.update_barriers(shape, false, barriers.into_iter());
+    }
+
+    #[test]
+    fn press_jump_on_ground() {
+        let mut player = new_player();
+        touch_floor(&mut player, 0., true);
+        assert!(player.on_ground);
+        player.press_jump();
+        assert!(player.queued_jump);
+        assert!(player.jump_held);
+    }
+
+    #[test]
+    fn press_jump_in_air() {
+        let mut player = new_player();
+        touch_floor(&mut player, 1., false);
+        player.press_jump();
+        assert!(!player.queued_jump);
+    }
+
+    #[test]
+    fn coyote_time() {
+        let physics = PhysicsProfile::standard();
+        let mut player = new_player();
+        touch_floor(&mut player, 0., true);
+        touch_floor(&mut player, 1., false);
+        player.set_time(1. + 0.5 * physics.coyote_duration);
+        player.press_jump();
+        assert!(player.queued_jump);
+    }
+
+    #[test]
+    fn coyote_time_expires() {
+        let physics = PhysicsProfile::standard();
+        let mut player = new_player();
+        touch_floor(&mut player, 0., true);
+        touch_floor(&mut player, 1., false);
+        player.set_time(1. + 2. * physics.coyote_duration);
+        player.press_jump();
+        assert!(!player.queued_jump);
+    }
+
+    #[test]
+    fn jump_buffer() {
+        let physics = PhysicsProfile::standard();
+        let mut player = new_player();
+        touch_floor(&mut player, 1., false);
+        player.press_jump();
+        touch_floor(&mut player, 1. + 0.5 * physics.jump_buffer_duration, true);
+        assert!(player.queued_jump);
+        assert!(player.jump_held);
+    }
+
+    #[test]
+    fn jump_buffer_expires() {
+        let physics = PhysicsProfile::standard();
+        let mut player = new_player();
+        touch_floor(&mut player, 1., false);
+        player.press_jump();
+        touch_floor(&mut player, 1. + 2. * physics.jump_buffer_duration, true);
+        assert!(!player.queued_jump);
+    }
+
+    #[test]
+    fn drop_through_one_way_floor() {
+        let mut player = new_player();
+        touch_surface(&mut player, 0., Some(Surface::one_way(Material::Normal)));
+        player.press_down();
+        assert!(player.queued_drop);
+    }
+
+    #[test]
+    fn no_drop_on_solid_ground() {
+        let mut player = new_player();
+        touch_floor(&mut player, 0., true);
+        player.press_down();
+        assert!(!player.queued_drop);
+    }
+}
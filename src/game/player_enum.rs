@@ -26,19 +26,23 @@ use super::player::Player;
 use super::star;
 use super::piece_profile::PieceProfile;
 use super::warp::WarpColor;
+use super::ability::Abilities;
+use super::key::Inventory;
 
 const START_FADE_VEL: f64 = 1. / 0.6;
 const START_DELAY: f64 = 0.7;
 const WARP_SPEED: f64 = 180.;
+const DYING_DURATION: f64 = 0.6;
 
 pub enum PlayerEnum {
-    Start(Vec2), Normal(Player), Warping(PlayerWarping), Complete(PlayerComplete)
+    Start(Vec2), Normal(Box<Player>), Warping(PlayerWarping), Complete(PlayerComplete), Dying(PlayerDying)
 }
 
 impl PlayerEnum {
     pub fn transition_time(&self) -> f64 {
         match *self {
             PlayerEnum::Warping(ref w) => w.end_time,
+            PlayerEnum::Dying(ref d) => d.death_time + DYING_DURATION,
             PlayerEnum::Start(_) => START_DELAY,
             _ => f64::INFINITY,
         }
@@ -59,15 +63,30 @@ impl PlayerEnum {
                 let fade = (START_DELAY - time) * START_FADE_VEL;
                 if fade <= 1. { renderer.draw_flash(&affine, SpriteId::PlayerRun, 0.5 + 0.6 * fade) }
             },
+            PlayerEnum::Dying(ref player) => {
+                let fade = (time - player.death_time) / DYING_DURATION;
+                let affine = if player.mirror { affine.pre_scale_axes(-1., 1.) } else { affine };
+                renderer.draw_flash(&affine.pre_scale_axes(1. + fade, 1. - fade), player.tex, fade);
+            },
             PlayerEnum::Warping(_) => {},
         }
     }
 
+    // the player's inventory is shown while they are playing or warping
+    pub fn inventory(&self) -> Option<&Inventory> {
+        match *self {
+            PlayerEnum::Normal(ref player) => Some(player.inventory()),
+            PlayerEnum::Warping(ref player) => Some(&player.inventory),
+            _ => None,
+        }
+    }
+
     pub fn pos(&self, collider: &Collider<PieceProfile>) -> Vec2 {
         match *self {
             PlayerEnum::Normal(ref player) => collider.get_hitbox(player.id()).value.pos,
             PlayerEnum::Start(pos) => pos,
             PlayerEnum::Complete(ref player) => player.pos,
+            PlayerEnum::Dying(ref player) => player.pos,
             PlayerEnum::Warping(ref player) => player.end_pos - player.vel * (player.end_time - collider.time()),
         }
     }
@@ -81,14 +100,40 @@ impl PlayerComplete {
     }
 }
 
-pub struct PlayerWarping { end_time: f64, color: WarpColor, end_pos: Vec2, vel: Vec2 }
+pub struct PlayerDying { pos: Vec2, respawn_pos: Vec2, death_time: f64, tex: SpriteId, mirror: bool }
+
+impl PlayerDying {
+    pub fn new(pos: Vec2, respawn_pos: Vec2, death_time: f64, tex: SpriteId, mirror: bool) -> PlayerDying {
+        PlayerDying { pos, respawn_pos, death_time, tex, mirror }
+    }
+
+    pub fn respawn_pos(&self) -> Vec2 { self.respawn_pos }
+}
+
+pub struct PlayerWarping {
+    end_time: f64,
+    color: WarpColor,
+    end_pos: Vec2,
+    vel: Vec2,
+    abilities: Abilities,
+    inventory: Inventory,
+    exit_vel: Vec2,
+}
 
 impl PlayerWarping {
-    pub fn new(start_pos: Vec2, end_pos: Vec2, color: WarpColor, time: f64) -> PlayerWarping {
+    pub fn new(start_pos: Vec2, end_pos: Vec2, color: WarpColor, time: f64, abilities: Abilities,
+               inventory: Inventory) -> PlayerWarping {
         let delta_pos = end_pos - start_pos;
         let dir = delta_pos.normalize().unwrap_or(Vec2::zero());
-        PlayerWarping { color, end_pos, vel: dir * WARP_SPEED, end_time: time + delta_pos.len() / WARP_SPEED }
+        let end_time = time + delta_pos.len() / WARP_SPEED;
+        PlayerWarping { color, end_pos, vel: dir * WARP_SPEED, end_time, abilities, inventory, exit_vel: Vec2::zero() }
     }
 
+    // the player normally arrives at rest, but keeps their momentum when travelling through a portal
+    pub fn with_exit_vel(self, exit_vel: Vec2) -> PlayerWarping { PlayerWarping { exit_vel, ..self } }
+
     pub fn color(&self) -> WarpColor { self.color }
+    pub fn abilities(&self) -> Abilities { self.abilities }
+    pub fn inventory(&self) -> &Inventory { &self.inventory }
+    pub fn exit_vel(&self) -> Vec2 { self.exit_vel }
 }
@@ -0,0 +1,74 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::f64;
+
+use gate::renderer::{SpriteRenderer, Affine};
+
+use collider::Hitbox;
+use collider::geom::{Card, Shape, Vec2, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::Idx2;
+use super::util::idx_to_vec;
+use super::warp::WarpColor;
+
+pub const COOLDOWN: f64 = 0.5;
+
+const EXIT_OFFSET: f64 = 2.;
+const PULSE_SPEED: f64 = 6.;
+
+// portals come in pairs of the same color, each facing away from the wall that it is placed against
+pub struct Portal { color: WarpColor, pos: Vec2, card: Card }
+
+impl Portal {
+    pub fn new(color: WarpColor, pos: Idx2, card: Card) -> Portal { Portal { color, pos: idx_to_vec(pos), card } }
+
+    pub fn color(&self) -> WarpColor { self.color }
+
+    pub fn hitbox(&self) -> Hitbox {
+        let dims = if self.card == Card::PlusX || self.card == Card::MinusX { v2(4., 12.) } else { v2(12., 4.) };
+        Shape::rect(dims).place(self.pos).still()
+    }
+
+    // the player comes out a little in front of the portal, so as not to overlap the wall behind it
+    pub fn exit_pos(&self) -> Vec2 {
+        let card_vec: Vec2 = self.card.into();
+        self.pos + card_vec * EXIT_OFFSET
+    }
+
+    // velocity going into this portal comes out of the exit portal turned by the difference in their facing,
+    // so that moving straight into one portal means moving straight out of the other
+    pub fn carry_vel(&self, exit: &Portal, vel: Vec2) -> Vec2 {
+        vel.rotate(exit.angle() - self.angle() + f64::consts::PI)
+    }
+
+    fn angle(&self) -> f64 {
+        let card_vec: Vec2 = self.card.into();
+        card_vec.y.atan2(card_vec.x)
+    }
+
+    // portals are drawn faded while cooling down
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64, ready: bool) {
+        let tex = match self.color {
+            WarpColor::Green => SpriteId::GreenPortal,
+            WarpColor::Blue => SpriteId::BluePortal,
+            WarpColor::Pink => SpriteId::PinkPortal,
+        };
+        let affine = affine.pre_rotate(self.angle()).pre_scale_axes(1. + 0.15 * (PULSE_SPEED * time).sin(), 1.);
+        renderer.draw_flash(&affine, tex, if ready { 0. } else { 0.5 });
+    }
+}
@@ -0,0 +1,66 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::f64;
+
+use gate::renderer::SpriteRenderer;
+
+use collider::Hitbox;
+use collider::geom::{Shape, Vec2, v2, Card};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::{CELL_LEN, Idx2};
+use super::util::{idx_to_vec, vec_to_affine};
+
+const WIDTH: f64 = CELL_LEN as f64 - 0.1;
+const HEIGHT: f64 = 6.;
+const COMPRESS_DURATION: f64 = 0.06;
+const EXTEND_DURATION: f64 = 0.2;
+
+// springs face away from the wall they are attached to, launching the player in that direction
+pub struct Spring { pos: Vec2, card: Card, launch_time: f64 }
+
+impl Spring {
+    pub fn new(pos: Idx2, card: Card) -> Spring {
+        Spring { pos: idx_to_vec(pos), card, launch_time: f64::NEG_INFINITY }
+    }
+
+    pub fn card(&self) -> Card { self.card }
+
+    pub fn hitbox(&self) -> Hitbox {
+        let card_vec: Vec2 = self.card.into();
+        let dims = if card_vec.x == 0. { v2(WIDTH, HEIGHT) } else { v2(HEIGHT, WIDTH) };
+        let offset = card_vec * (-0.5 * (CELL_LEN as f64 - HEIGHT));
+        Shape::rect(dims).place(self.pos + offset).still()
+    }
+
+    pub fn launch(&mut self, time: f64) { self.launch_time = time; }
+
+    // sprites are drawn facing upward, and rotated to face the spring's direction
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, camera: Vec2, time: f64) {
+        let elapsed = time - self.launch_time;
+        let tex = if elapsed < COMPRESS_DURATION {
+            SpriteId::SpringCompressed
+        } else if elapsed < COMPRESS_DURATION + EXTEND_DURATION {
+            SpriteId::SpringExtended
+        } else {
+            SpriteId::Spring
+        };
+        let card_vec: Vec2 = self.card.into();
+        let angle = card_vec.y.atan2(card_vec.x) - 0.5 * f64::consts::PI;
+        renderer.draw(&vec_to_affine(self.pos - camera).pre_rotate(angle), tex);
+    }
+}
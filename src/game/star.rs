@@ -39,6 +39,8 @@ impl Star {
 
     pub fn obtain(&mut self, time: f64) { self.obtain_time = time; }
 
+    pub fn is_obtained(&self, time: f64) -> bool { time >= self.obtain_time }
+
     pub fn level_end_time(&self) -> f64 { self.obtain_time + 1.85 }
 
     pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
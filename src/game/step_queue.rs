@@ -14,20 +14,27 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::player;
+use super::{player, enemy};
+
+pub const LASOR_UPDATE_PERIOD: f64 = 1. / 30.;
 
 #[derive(Copy, Clone)]
-pub enum Step { Player, Platform, WarpEffectSpawn, LasorFire }
-const STEP_COUNT: usize = 4;
-const STEPS: [Step; STEP_COUNT] = [Step::Player, Step::Platform, Step::WarpEffectSpawn, Step::LasorFire ];
+pub enum Step { Player, Platform, EffectSpawn, Logic, LasorUpdate, Enemy, Crate }
+const STEP_COUNT: usize = 7;
+const STEPS: [Step; STEP_COUNT] = [
+    Step::Player, Step::Platform, Step::EffectSpawn, Step::Logic, Step::LasorUpdate, Step::Enemy, Step::Crate,
+];
 
 impl Step {
     fn period(self) -> f64 {
         match self {
             Step::Player => player::STEP_PERIOD,
             Step::Platform => 0.18,
-            Step::WarpEffectSpawn => 1. / 30.,
-            Step::LasorFire => 1.,
+            Step::EffectSpawn => 1. / 30.,
+            Step::Logic => 0.1,
+            Step::LasorUpdate => LASOR_UPDATE_PERIOD,
+            Step::Enemy => enemy::STEP_PERIOD,
+            Step::Crate => player::STEP_PERIOD,
         }
     }
 }
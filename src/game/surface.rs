@@ -0,0 +1,32 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use collider::geom::Vec2;
+
+// material on top of a barrier, which changes how the player accelerates when standing on it
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Material { Normal, Ice, Mud }
+
+// properties of a barrier's top that are imparted on the player standing on it,
+// and whether it is a one-way floor that the player can drop through
+#[derive(Copy, Clone)]
+pub struct Surface { pub vel: Vec2, pub material: Material, pub one_way: bool }
+
+impl Surface {
+    pub fn new(vel: Vec2, material: Material) -> Surface { Surface { vel, material, one_way: false } }
+    pub fn plain() -> Surface { Surface::new(Vec2::zero(), Material::Normal) }
+    pub fn one_way(material: Material) -> Surface { Surface { one_way: true, ..Surface::new(Vec2::zero(), material) } }
+}
@@ -16,9 +16,9 @@
 
 use gate::renderer::Affine;
 
-use collider::geom::{Vec2, v2, Card};
+use collider::geom::{Vec2, v2, Card, PlacedShape};
 
-use super::Idx2;
+use super::{CELL_LEN, Idx2};
 
 pub struct IdGen { next: u64 }
 
@@ -32,10 +32,15 @@ impl IdGen {
 }
 
 pub fn idx_to_vec(idx: Idx2) -> Vec2 {
-    fn idx_to_f64(idx: i32) -> f64 { (idx * 8 + 4) as f64 }
+    fn idx_to_f64(idx: i32) -> f64 { (idx * CELL_LEN + CELL_LEN / 2) as f64 }
     v2(idx_to_f64(idx.0), idx_to_f64(idx.1))
 }
 
+pub fn vec_to_idx(vec: Vec2) -> Idx2 {
+    let len = CELL_LEN as f64;
+    ((vec.x / len).floor() as i32, (vec.y / len).floor() as i32)
+}
+
 pub fn vec_to_affine(vec: Vec2) -> Affine { Affine::translate(vec.x, vec.y) }
 
 pub fn card_offset(card: Card) -> Idx2 {
@@ -47,6 +52,24 @@ pub fn card_offset(card: Card) -> Idx2 {
     }
 }
 
+// fraction of the way from start to end at which the segment first enters the rectangle, if it does at all
+pub fn segment_entry(start: Vec2, end: Vec2, rect: &PlacedShape) -> Option<f64> {
+    let delta = end - start;
+    let (mut enter, mut exit) = (0_f64, 1_f64);
+    let axes = [(start.x, delta.x, rect.pos.x, rect.dims().x), (start.y, delta.y, rect.pos.y, rect.dims().y)];
+    for &(start, delta, pos, len) in axes.iter() {
+        let (min, max) = (pos - 0.5 * len, pos + 0.5 * len);
+        if delta == 0. {
+            if start <= min || start >= max { return None; }
+        } else {
+            let (t_1, t_2) = ((min - start) / delta, (max - start) / delta);
+            enter = enter.max(t_1.min(t_2));
+            exit = exit.min(t_1.max(t_2));
+        }
+    }
+    if enter < exit { Some(enter) } else { None }
+}
+
 pub fn nearest_card(vector: Vec2) -> Card {
     if vector.x.abs() > vector.y.abs() {
         if vector.x > 0.0 { Card::PlusX } else { Card::MinusX }
@@ -54,3 +77,31 @@ pub fn nearest_card(vector: Vec2) -> Card {
         if vector.y > 0.0 { Card::PlusY } else { Card::MinusY }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use collider::geom::{Shape, v2};
+
+    use super::segment_entry;
+
+    #[test]
+    fn segment_entry_hits() {
+        let rect = Shape::square(8.).place(v2(10., 0.));
+        assert_eq!(segment_entry(v2(0., 0.), v2(20., 0.), &rect), Some(0.3));
+        assert_eq!(segment_entry(v2(0., -10.), v2(20., 10.), &rect), Some(0.3));
+    }
+
+    #[test]
+    fn segment_entry_misses() {
+        let rect = Shape::square(8.).place(v2(10., 0.));
+        assert_eq!(segment_entry(v2(0., 0.), v2(5., 0.), &rect), None);
+        assert_eq!(segment_entry(v2(0., 4.), v2(20., 4.), &rect), None);
+        assert_eq!(segment_entry(v2(0., 10.), v2(20., 12.), &rect), None);
+    }
+
+    #[test]
+    fn segment_entry_from_inside() {
+        let rect = Shape::square(8.).place(v2(10., 0.));
+        assert_eq!(segment_entry(v2(10., 0.), v2(30., 0.), &rect), Some(0.));
+    }
+}
@@ -15,13 +15,15 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::f64;
+use std::collections::HashMap;
 
 use gate::renderer::{SpriteRenderer, Affine};
 
-use collider::geom::{Shape, Vec2, v2, Card};
+use collider::HbId;
+use collider::geom::{Shape, PlacedShape, Vec2, v2, Card};
 
 use crate::asset_id::{AssetId, SpriteId};
-use super::Idx2;
+use super::{CELL_LEN, Idx2};
 use super::util::{idx_to_vec, vec_to_affine};
 
 const SPIN_VEL: f64 = -4.;
@@ -30,10 +32,27 @@ const SPIN_VEL: f64 = -4.;
 pub enum WarpColor { Green, Blue, Pink }
 
 impl WarpColor {
+    pub fn from_index(index: u32) -> Option<WarpColor> {
+        match index {
+            0 => Some(WarpColor::Green),
+            1 => Some(WarpColor::Blue),
+            2 => Some(WarpColor::Pink),
+            _ => None,
+        }
+    }
+
     pub fn draw_warp(self, renderer: &mut SpriteRenderer<AssetId>, affine: Affine, time: f64) {
         renderer.draw(&affine.pre_rotate(time * SPIN_VEL), self.tex());
     }
 
+    fn beam_tex(self) -> SpriteId {
+        match self {
+            WarpColor::Green => SpriteId::GreenBeam,
+            WarpColor::Blue => SpriteId::BlueBeam,
+            WarpColor::Pink => SpriteId::PinkBeam,
+        }
+    }
+
     fn tex(self) -> SpriteId {
         match self {
             WarpColor::Green => SpriteId::GreenWarp,
@@ -45,33 +64,300 @@ impl WarpColor {
 
 pub fn shape() -> Shape { Shape::circle(7.) }
 
+const WARP_FALL_ACCEL: f64 = 90.;
+const WARP_HOMING_TURN_SPEED: f64 = 1.5;
+
+// lobbed warps arc downwards under gravity, while homing warps steer towards the player
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Flight { Straight, Lobbed, Homing }
+
+impl Flight {
+    fn parse(name: &str) -> Option<Flight> {
+        match name {
+            "straight" => Some(Flight::Straight),
+            "lobbed" => Some(Flight::Lobbed),
+            "homing" => Some(Flight::Homing),
+            _ => None,
+        }
+    }
+
+    // returns the velocity of a warp after flying for the elapsed time
+    pub fn steer(self, vel: Vec2, to_player: Vec2, elapsed: f64) -> Vec2 {
+        match self {
+            Flight::Straight => vel,
+            Flight::Lobbed => vel - v2(0., WARP_FALL_ACCEL * elapsed),
+            Flight::Homing => {
+                let angle_delta = wrap_angle(to_player.y.atan2(to_player.x) - vel.y.atan2(vel.x));
+                let max_turn = WARP_HOMING_TURN_SPEED * elapsed;
+                vel.rotate(angle_delta.max(-max_turn).min(max_turn))
+            },
+        }
+    }
+}
+
+// a warp reflects off walls and floors while it has bounces left, and vanishes once it expires
 #[derive(Copy, Clone)]
-pub enum LasorKind { Still, Aiming }
+pub struct Warp { pub color: WarpColor, pub flight: Flight, pub bounces: u32, pub expire_time: f64 }
+
+impl Warp {
+    pub fn standing(color: WarpColor) -> Warp {
+        Warp { color, flight: Flight::Straight, bounces: 0, expire_time: f64::INFINITY }
+    }
+}
+
+// aiming lasors only track and fire at the player while they have a clear line of sight,
+// and otherwise sweep back and forth searching for the player,
+// while beam lasors emit a continuous beam up to the first wall for part of each period
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum LasorKind { Still, Aiming, Beam }
 
 impl LasorKind {
     fn max_angle(self) -> f64 {
         match self {
-            LasorKind::Still => 0.,
+            LasorKind::Still | LasorKind::Beam => 0.,
             LasorKind::Aiming => 30_f64.to_radians(),
         }
     }
 }
 
-const LASOR_FIRE_SPEED: f64 = 60.;
 const LASOR_FIRE_OFFSET: f64 = 7.;
+const LASOR_CHARGE_DURATION: f64 = 0.4;
+const LASOR_BURST_INTERVAL: f64 = 0.15;
+const LASOR_TRACK_SPEED: f64 = 4.;
+const LASOR_SWEEP_SPEED: f64 = 0.8;
+const LASOR_STUN_FLICKER_SPEED: f64 = 30.;
+const BEAM_WIDTH: f64 = 3.;
+const BEAM_TELEGRAPH_DURATION: f64 = 0.6;
+const BEAM_TELEGRAPH_SCALE: f64 = 0.2;
+const BEAM_FLICKER_SPEED: f64 = 40.;
+
+// a lasor fires a burst of shots every period, starting at its phase offset,
+// and a beam lasor instead keeps its beam on for the given duration
+#[derive(Copy, Clone)]
+pub struct LasorSchedule {
+    period: f64,
+    phase: f64,
+    burst: u32,
+    speed: f64,
+    duration: f64,
+    flight: Flight,
+    bounces: u32,
+    lifetime: f64,
+}
+
+impl LasorSchedule {
+    pub fn standard() -> LasorSchedule {
+        LasorSchedule {
+            period: 1.,
+            phase: 0.,
+            burst: 1,
+            speed: 60.,
+            duration: 0.5,
+            flight: Flight::Straight,
+            bounces: 0,
+            lifetime: f64::INFINITY,
+        }
+    }
+
+    // each line is a lasor's position followed by the values that differ from the standard schedule,
+    // e.g. "12 5 period=2 phase=0.5 burst=3 speed=80 flight=homing"
+    pub fn parse(contents: &str) -> Result<HashMap<Idx2, LasorSchedule>, String> {
+        let mut schedules = HashMap::new();
+        for (line_num, line) in contents.lines().enumerate() {
+            let err = || format!("line {}: expected \"x y name=value ...\"", line_num + 1);
+            let mut words = line.split('#').next().unwrap().split_whitespace();
+            let x = match words.next() { Some(x) => x, None => continue };
+            let x = x.parse::<i32>().map_err(|_| err())?;
+            let y = words.next().and_then(|y| y.parse::<i32>().ok()).ok_or_else(err)?;
+            let mut schedule = LasorSchedule::standard();
+            for word in words {
+                let mut parts = word.splitn(2, '=');
+                let (name, value) = (parts.next().unwrap(), parts.next().ok_or_else(err)?);
+                if name == "flight" {
+                    schedule.flight = Flight::parse(value).ok_or_else(err)?;
+                    continue;
+                }
+                let value = value.parse::<f64>().map_err(|_| err())?;
+                let whole = || if value >= 0. && value.fract() == 0. {
+                    Ok(value as u32)
+                } else {
+                    Err(format!("line {}: {} must be a whole number", line_num + 1, name))
+                };
+                match name {
+                    "period" => schedule.period = value,
+                    "phase" => schedule.phase = value,
+                    "burst" => schedule.burst = whole()?,
+                    "speed" => schedule.speed = value,
+                    "duration" => schedule.duration = value,
+                    "bounces" => schedule.bounces = whole()?,
+                    "lifetime" => schedule.lifetime = value,
+                    _ => return Err(format!("line {}: unknown value \"{}\"", line_num + 1, name)),
+                }
+            }
+            if schedule.burst == 0 || schedule.period <= LASOR_BURST_INTERVAL * (schedule.burst - 1) as f64 {
+                return Err(format!("line {}: burst does not fit in period", line_num + 1));
+            }
+            if schedule.phase < -schedule.period {
+                return Err(format!("line {}: phase must not be less than minus the period", line_num + 1));
+            }
+            if schedule.speed <= 0. {
+                return Err(format!("line {}: speed must be positive", line_num + 1));
+            }
+            if schedule.lifetime <= 0. {
+                return Err(format!("line {}: lifetime must be positive", line_num + 1));
+            }
+            if schedules.insert((x, y), schedule).is_some() {
+                return Err(format!("line {}: lasor at ({}, {}) is scheduled twice", line_num + 1, x, y));
+            }
+        }
+        Ok(schedules)
+    }
+
+    // only beam lasors use the duration, so it is checked once the kind of lasor is known
+    pub fn fits_beam(&self) -> bool { self.duration > 0. && self.duration < self.period }
+}
 
 fn angle_to_vec(angle: f64) -> Vec2 { v2(angle.cos(), angle.sin()) }
 
-pub struct Lasor { pos: Vec2, card: Card, max_angle: f64, color: WarpColor }
+// wraps an angle into the range [-pi, pi]
+fn wrap_angle(angle: f64) -> f64 {
+    let angle = angle % (2. * f64::consts::PI);
+    if angle > f64::consts::PI {
+        angle - 2. * f64::consts::PI
+    } else if angle < -f64::consts::PI {
+        angle + 2. * f64::consts::PI
+    } else {
+        angle
+    }
+}
+
+pub struct Lasor {
+    pos: Vec2,
+    card: Card,
+    kind: LasorKind,
+    color: WarpColor,
+    enabled: bool,
+    stun_end_time: Option<f64>,
+    schedule: LasorSchedule,
+    next_fire_time: f64,
+    burst_shot: u32,
+    aim_delta: f64,
+    sighted: bool,
+    sweep_dir: f64,
+    beam_on: bool,
+    beam_end_time: f64,
+    beam_length: f64,
+    beam_id: Option<HbId>,
+}
 
 impl Lasor {
-    pub fn new(pos: Idx2, kind: LasorKind, color: WarpColor, card: Card) -> Lasor {
-        Lasor { card, color, pos: idx_to_vec(pos), max_angle: kind.max_angle() }
+    pub fn new(pos: Idx2, kind: LasorKind, color: WarpColor, card: Card, schedule: LasorSchedule) -> Lasor {
+        Lasor {
+            card, kind, color, schedule,
+            pos: idx_to_vec(pos),
+            enabled: true,
+            stun_end_time: None,
+            next_fire_time: schedule.period + schedule.phase,
+            burst_shot: 0,
+            aim_delta: 0.,
+            sighted: false,
+            sweep_dir: 1.,
+            beam_on: false,
+            beam_end_time: f64::NEG_INFINITY,
+            beam_length: 0.,
+            beam_id: None,
+        }
     }
 
-    pub fn fire(&self, player_pos: Vec2) -> (Vec2, WarpColor, Vec2) {
-        let angle = self.angle(player_pos);
-        (self.fire_pos(angle), self.color, angle_to_vec(angle) * LASOR_FIRE_SPEED)
+    pub fn pos(&self) -> Vec2 { self.pos }
+    pub fn is_aiming(&self) -> bool { self.kind == LasorKind::Aiming }
+    pub fn is_beam(&self) -> bool { self.kind == LasorKind::Beam }
+
+    // still lasors and beams fire blindly, while aiming lasors hold their fire until they can see the player
+    pub fn can_fire(&self) -> bool { self.is_active() && (self.kind != LasorKind::Aiming || self.sighted) }
+
+    // a stunned lasor neither fires nor aims, but its schedule carries on
+    fn is_active(&self) -> bool { self.enabled && self.stun_end_time.is_none() }
+    pub fn stun(&mut self, end_time: f64) { self.stun_end_time = Some(end_time); }
+
+    // returns true if the stun has just worn off
+    pub fn update_stun(&mut self, time: f64) -> bool {
+        match self.stun_end_time {
+            Some(end_time) if time >= end_time => {
+                self.stun_end_time = None;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // turns an aiming lasor towards the player if sighted, or along its search sweep otherwise
+    pub fn aim(&mut self, player_pos: Vec2, sighted: bool, elapsed: f64) {
+        if !self.is_aiming() || self.stun_end_time.is_some() { return; }
+        self.sighted = sighted;
+        let max_angle = self.kind.max_angle();
+        let (target, speed) = if sighted {
+            (self.target_delta(player_pos), LASOR_TRACK_SPEED)
+        } else {
+            (self.sweep_dir * max_angle, LASOR_SWEEP_SPEED)
+        };
+        let step = speed * elapsed;
+        if (target - self.aim_delta).abs() <= step {
+            self.aim_delta = target;
+            if !sighted { self.sweep_dir = -self.sweep_dir; }
+        } else {
+            self.aim_delta += step * (target - self.aim_delta).signum();
+        }
+    }
+
+    pub fn color(&self) -> WarpColor { self.color }
+    pub fn set_enabled(&mut self, enabled: bool) { self.enabled = enabled; }
+
+    // for a beam that is on, this is instead the time that it switches off
+    pub fn next_fire_time(&self) -> f64 {
+        if self.beam_on { self.beam_end_time } else { self.next_fire_time }
+    }
+
+    pub fn switch_beam(&mut self) {
+        self.beam_on = !self.beam_on;
+        if self.beam_on {
+            self.beam_end_time = self.next_fire_time + self.schedule.duration;
+            self.next_fire_time += self.schedule.period;
+        }
+    }
+
+    // the beam is only shown, and only has a hitbox, while the lasor is also enabled and not stunned
+    pub fn is_beam_shown(&self) -> bool { self.beam_on && self.is_active() }
+    pub fn beam_start(&self) -> Vec2 { self.fire_pos(self.angle()) }
+    pub fn beam_dir(&self) -> Vec2 { self.card.into() }
+    pub fn beam_length(&self) -> f64 { self.beam_length }
+    pub fn set_beam_length(&mut self, length: f64) { self.beam_length = length; }
+    pub fn beam_id(&self) -> Option<HbId> { self.beam_id }
+    pub fn set_beam_id(&mut self, id: Option<HbId>) { self.beam_id = id; }
+
+    pub fn beam_shape(&self) -> PlacedShape {
+        let dir = self.beam_dir();
+        let dims = if dir.x == 0. { v2(BEAM_WIDTH, self.beam_length) } else { v2(self.beam_length, BEAM_WIDTH) };
+        Shape::rect(dims).place(self.beam_start() + dir * (0.5 * self.beam_length))
+    }
+
+    pub fn fire(&mut self) -> (Vec2, Warp, Vec2) {
+        let expire_time = self.next_fire_time + self.schedule.lifetime;
+        let warp = Warp { color: self.color, flight: self.schedule.flight, bounces: self.schedule.bounces, expire_time };
+        self.skip();
+        let angle = self.angle();
+        (self.fire_pos(angle), warp, angle_to_vec(angle) * self.schedule.speed)
+    }
+
+    // advances the lasor to its next shot without firing, so that it keeps to its schedule even while disabled
+    pub fn skip(&mut self) {
+        self.burst_shot += 1;
+        if self.burst_shot < self.schedule.burst {
+            self.next_fire_time += LASOR_BURST_INTERVAL;
+        } else {
+            self.next_fire_time += self.schedule.period - LASOR_BURST_INTERVAL * (self.schedule.burst - 1) as f64;
+            self.burst_shot = 0;
+        }
     }
 
     fn fire_pos(&self, angle: f64) -> Vec2 {
@@ -83,17 +369,12 @@ impl Lasor {
         card_vec.y.atan2(card_vec.x)
     }
 
-    fn angle(&self, player_pos: Vec2) -> f64 {
+    fn angle(&self) -> f64 { self.support_angle() + self.aim_delta }
+
+    fn target_delta(&self, player_pos: Vec2) -> f64 {
         let rel_player = player_pos - self.pos;
         let support_angle = self.support_angle();
-        let angle_delta = (rel_player.y.atan2(rel_player.x) - support_angle) % (2. * f64::consts::PI);
-        let angle_delta = if angle_delta > f64::consts::PI {
-            angle_delta - 2. * f64::consts::PI
-        } else if angle_delta < -f64::consts::PI {
-            angle_delta + 2. * f64::consts::PI
-        } else {
-            angle_delta
-        };
+        let angle_delta = wrap_angle(rel_player.y.atan2(rel_player.x) - support_angle);
         let angle_delta = if angle_delta > 0.5 * f64::consts::PI {
             f64::consts::PI - angle_delta
         } else if angle_delta < -0.5 * f64::consts::PI {
@@ -101,21 +382,45 @@ impl Lasor {
         } else {
             angle_delta
         };
-        let angle_delta = angle_delta.max(-self.max_angle).min(self.max_angle);
-        support_angle + angle_delta
+        let max_angle = self.kind.max_angle();
+        angle_delta.max(-max_angle).min(max_angle)
     }
 
-    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, camera: Vec2, time: f64, next_fire_time: f64, player_pos: Vec2) {
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, camera: Vec2, time: f64) {
         renderer.draw(&vec_to_affine(self.pos - camera).pre_rotate(self.support_angle() + f64::consts::PI), SpriteId::TileR2C3);
-        let angle = self.angle(player_pos);
+        let angle = self.angle();
         let lasor_affine = vec_to_affine(self.pos - camera).pre_rotate(angle + f64::consts::PI);
-        renderer.draw(&lasor_affine, SpriteId::Lasor);
-        let ratio = 1. - (next_fire_time - time) * 2.5;
-        if ratio > 0. {
+        if self.stun_end_time.is_some() {
+            renderer.draw_flash(&lasor_affine, SpriteId::Lasor, 0.5 + 0.5 * (time * LASOR_STUN_FLICKER_SPEED).sin());
+        } else {
+            renderer.draw(&lasor_affine, SpriteId::Lasor);
+        }
+        if self.is_beam() {
+            self.draw_beam(renderer, camera, time);
+            return;
+        }
+        let charge_duration = LASOR_CHARGE_DURATION.min(self.schedule.period);
+        let ratio = 1. - (self.next_fire_time - time) / charge_duration;
+        if ratio > 0. && self.can_fire() {
             let scale = 0.2 + 0.8 * ratio;
             let flash = 1.0 - 0.5 * ratio;
             let affine = vec_to_affine(self.fire_pos(angle) - camera).pre_scale(scale).pre_rotate(time * SPIN_VEL);
             renderer.draw_flash(&affine, self.color.tex(), flash);
         }
     }
+
+    // shortly before switching on, a beam flickers as a thin line to warn the player
+    fn draw_beam(&self, renderer: &mut SpriteRenderer<AssetId>, camera: Vec2, time: f64) {
+        let telegraph = !self.beam_on && self.can_fire() && self.next_fire_time - time < BEAM_TELEGRAPH_DURATION;
+        if !self.is_beam_shown() && !telegraph { return; }
+        let (width_scale, flash) = if telegraph {
+            (BEAM_TELEGRAPH_SCALE, 0.5 + 0.5 * (time * BEAM_FLICKER_SPEED).sin())
+        } else {
+            (1., 0.)
+        };
+        let center = self.beam_start() + self.beam_dir() * (0.5 * self.beam_length);
+        let affine = vec_to_affine(center - camera).pre_rotate(self.support_angle())
+                                                   .pre_scale_axes(self.beam_length / CELL_LEN as f64, width_scale);
+        renderer.draw_flash(&affine, self.color.beam_tex(), flash);
+    }
 }
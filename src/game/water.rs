@@ -0,0 +1,54 @@
+// chirperjax, a demo game built using the "gate" game library.
+// Copyright (C) 2017-2019  Matthew D. Michelotti
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use gate::renderer::SpriteRenderer;
+
+use collider::Hitbox;
+use collider::geom::{Shape, Vec2, v2};
+
+use crate::asset_id::{AssetId, SpriteId};
+use super::{CELL_LEN, Idx2};
+use super::util::{idx_to_vec, vec_to_affine};
+
+const SURFACE_DEPTH: f64 = 5.;
+const SURFACE_FRAME_DURATION: f64 = 0.4;
+const SURFACE_FRAMES: [SpriteId; 2] = [SpriteId::WaterSurface, SpriteId::WaterSurface2];
+
+// the top cell of a body of water is its animated surface, which only counts as water below the waterline
+pub struct WaterCell { pos: Vec2, surface: bool }
+
+impl WaterCell {
+    pub fn new(pos: Idx2, surface: bool) -> WaterCell { WaterCell { pos: idx_to_vec(pos), surface } }
+
+    pub fn hitbox(&self) -> Hitbox {
+        let len = CELL_LEN as f64;
+        if self.surface {
+            Shape::rect(v2(len, SURFACE_DEPTH)).place(self.pos - v2(0., 0.5 * (len - SURFACE_DEPTH))).still()
+        } else {
+            Shape::square(len).place(self.pos).still()
+        }
+    }
+
+    // neighboring surface cells are drawn out of phase to make the surface ripple
+    pub fn draw(&self, renderer: &mut SpriteRenderer<AssetId>, camera: Vec2, time: f64) {
+        let tex = if self.surface {
+            SURFACE_FRAMES[(time / SURFACE_FRAME_DURATION + self.pos.x / CELL_LEN as f64) as usize % SURFACE_FRAMES.len()]
+        } else {
+            SpriteId::Water
+        };
+        renderer.draw(&vec_to_affine(self.pos - camera), tex);
+    }
+}
@@ -27,6 +27,13 @@ impl HorizDir {
         }
     }
 
+    pub fn flip(self) -> HorizDir {
+        match self {
+            HorizDir::Left => HorizDir::Right,
+            HorizDir::Right => HorizDir::Left,
+        }
+    }
+
     fn from_key(key: KeyCode) -> Option<HorizDir> {
         match key {
             KeyCode::Left => Some(HorizDir::Left),
@@ -40,6 +47,10 @@ pub enum InputEvent {
     UpdateMovement(Option<HorizDir>),
     PressJump,
     ReleaseJump,
+    PressDown,
+    ReleaseDown,
+    PressDash,
+    PressChirp,
 }
 
 pub struct GameInput { held_dirs: Vec<HorizDir> }
@@ -53,6 +64,12 @@ impl GameInput {
             Some(InputEvent::UpdateMovement(Some(dir)))
         } else if key == KeyCode::Up {
             Some(InputEvent::PressJump)
+        } else if key == KeyCode::Down {
+            Some(InputEvent::PressDown)
+        } else if key == KeyCode::X {
+            Some(InputEvent::PressDash)
+        } else if key == KeyCode::C {
+            Some(InputEvent::PressChirp)
         } else {
             None
         }
@@ -64,6 +81,8 @@ impl GameInput {
             Some(InputEvent::UpdateMovement(self.held_dir()))
         } else if key == KeyCode::Up {
             Some(InputEvent::ReleaseJump)
+        } else if key == KeyCode::Down {
+            Some(InputEvent::ReleaseDown)
         } else {
             None
         }
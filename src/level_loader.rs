@@ -14,9 +14,14 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::game::{GameBoard, LasorKind, PlatformKind, WarpColor, Idx2};
+use std::collections::HashMap;
+use std::fs;
 
-pub const LEVEL_COUNT: usize = 7;
+use crate::game::{GameBoard, LasorKind, PlatformKind, WarpColor, Ability, PhysicsProfile, Material, GravityZone, ButtonKind,
+                  Logic, LasorSchedule, EnemyKind, KeyColor, Idx2};
+use crate::game_input::HorizDir;
+
+pub const LEVEL_COUNT: usize = 8;
 
 const LEVELS: [&'static str; LEVEL_COUNT] = [
     include_str!("levels/level0.txt"),
@@ -26,6 +31,7 @@ const LEVELS: [&'static str; LEVEL_COUNT] = [
     include_str!("levels/level4.txt"),
     include_str!("levels/level5.txt"),
     include_str!("levels/level6.txt"),
+    include_str!("levels/level7.txt"),
 ];
 
 const LEVELS_INDEX: [&'static str; LEVEL_COUNT] = [
@@ -36,55 +42,150 @@ const LEVELS_INDEX: [&'static str; LEVEL_COUNT] = [
     include_str!("levels/level4_index.txt"),
     include_str!("levels/level5_index.txt"),
     include_str!("levels/level6_index.txt"),
+    include_str!("levels/level7_index.txt"),
+];
+
+// each logic file declares how the level's buttons and other signals control its pieces, see `Logic::parse`
+const LEVELS_LOGIC: [&str; LEVEL_COUNT] = [
+    include_str!("levels/level0_logic.txt"),
+    include_str!("levels/level1_logic.txt"),
+    include_str!("levels/level2_logic.txt"),
+    include_str!("levels/level3_logic.txt"),
+    include_str!("levels/level4_logic.txt"),
+    include_str!("levels/level5_logic.txt"),
+    include_str!("levels/level6_logic.txt"),
+    include_str!("levels/level7_logic.txt"),
+];
+
+// each lasor file lists the lasors that fire on other than the standard schedule, see `LasorSchedule::parse`
+const LEVELS_LASORS: [&str; LEVEL_COUNT] = [
+    include_str!("levels/level0_lasors.txt"),
+    include_str!("levels/level1_lasors.txt"),
+    include_str!("levels/level2_lasors.txt"),
+    include_str!("levels/level3_lasors.txt"),
+    include_str!("levels/level4_lasors.txt"),
+    include_str!("levels/level5_lasors.txt"),
+    include_str!("levels/level6_lasors.txt"),
+    include_str!("levels/level7_lasors.txt"),
 ];
 
+const PHYSICS: &str = include_str!("physics.txt");
+const PHYSICS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/physics.txt");
+
+// in debug builds the physics file is read from disk if possible, so that it can be tuned without recompiling
+pub fn load_physics(level_num: usize) -> Result<PhysicsProfile, String> {
+    let contents = if cfg!(debug_assertions) { fs::read_to_string(PHYSICS_PATH).ok() } else { None };
+    PhysicsProfile::parse(contents.as_deref().unwrap_or(PHYSICS), level_num)
+}
+
 pub fn load(level_num: usize) -> GameBoard {
     let level = LevelFile::new(LEVELS[level_num]);
     let level_index = LevelFile::new(LEVELS_INDEX[level_num]);
 
     let mut board = GameBoard::builder(level.dims);
+    let logic = Logic::parse(LEVELS_LOGIC[level_num]);
+    board.set_logic(logic.unwrap_or_else(|err| panic!("error reading logic for level {}, {}", level_num, err)));
+    let schedules = LasorSchedule::parse(LEVELS_LASORS[level_num]);
+    let mut schedules = schedules.unwrap_or_else(|err| panic!("error reading lasors for level {}, {}", level_num, err));
     for y in 0..level.dims.1 {
         for x in 0..level.dims.0 {
             let pos = (x, y);
+            let material = index_to_material(level_index.get(pos));
+            if let Some(zone) = index_to_gravity_zone(level_index.get(pos)) { board.add_gravity_zone(pos, zone); }
+            // platforms marked 'z' in the index file are dormant until the player chirps near them
+            if level_index.get(pos) == 'z' { board.set_dormant(pos); }
             match (level.get(pos), digit(level_index.get(pos))) {
                 ('P', None) => board.add_player(pos),
                 ('@', None) => board.add_star(pos),
                 ('-', None) => board.add_wall(pos),
                 ('+', None) => board.add_floor(pos),
+                ('}', None) => board.add_conveyor(pos, HorizDir::Right),
+                ('{', None) => board.add_conveyor(pos, HorizDir::Left),
+                ('=', None) => board.add_material(pos, Material::Ice),
+                ('%', None) => board.add_material(pos, Material::Mud),
                 ('I', Some(idx)) => board.add_gate(pos, idx),
-                ('C', idx) => board.add_platform(pos, PlatformKind::Circle, idx),
-                ('c', idx) => board.add_platform(pos, PlatformKind::ReverseCircle, idx),
-                ('A', idx) => board.add_platform(pos, PlatformKind::UpDown, idx),
-                ('V', idx) => board.add_platform(pos, PlatformKind::DownUp, idx),
-                ('>', idx) => board.add_platform(pos, PlatformKind::RightLeft, idx),
-                ('<', idx) => board.add_platform(pos, PlatformKind::LeftRight, idx),
-                ('L', Some(idx)) => board.add_lasor(pos, LasorKind::Still, index_to_color(idx)),
-                ('H', Some(idx)) => board.add_lasor(pos, LasorKind::Aiming, index_to_color(idx)),
-                ('B', Some(idx)) => board.add_button(pos, idx),
+                ('C', idx) => board.add_platform(pos, PlatformKind::Circle, material, idx),
+                ('c', idx) => board.add_platform(pos, PlatformKind::ReverseCircle, material, idx),
+                ('A', idx) => board.add_platform(pos, PlatformKind::UpDown, material, idx),
+                ('V', idx) => board.add_platform(pos, PlatformKind::DownUp, material, idx),
+                ('>', idx) => board.add_platform(pos, PlatformKind::RightLeft, material, idx),
+                ('<', idx) => board.add_platform(pos, PlatformKind::LeftRight, material, idx),
+                ('O', idx) => board.add_block(pos, PlatformKind::Circle, idx),
+                ('o', idx) => board.add_block(pos, PlatformKind::ReverseCircle, idx),
+                ('U', idx) => board.add_block(pos, PlatformKind::UpDown, idx),
+                ('D', idx) => board.add_block(pos, PlatformKind::DownUp, idx),
+                (']', idx) => board.add_block(pos, PlatformKind::RightLeft, idx),
+                ('[', idx) => board.add_block(pos, PlatformKind::LeftRight, idx),
+                ('L', Some(idx)) => board.add_lasor(pos, LasorKind::Still, index_to_color(idx), schedule(&mut schedules, pos)),
+                ('H', Some(idx)) => board.add_lasor(pos, LasorKind::Aiming, index_to_color(idx), schedule(&mut schedules, pos)),
+                ('E', Some(idx)) => board.add_lasor(pos, LasorKind::Beam, index_to_color(idx), schedule(&mut schedules, pos)),
+                ('B', Some(idx)) => board.add_button(pos, idx, ButtonKind::Once),
+                ('T', Some(idx)) => board.add_button(pos, idx, ButtonKind::Toggle),
+                ('t', Some(idx)) => board.add_button(pos, idx, ButtonKind::Timed),
+                ('J', None) => board.add_pickup(pos, Ability::DoubleJump),
+                ('X', None) => board.add_pickup(pos, Ability::AirDash),
+                ('K', Some(idx)) => board.add_key(pos, index_to_key_color(idx)),
+                ('Z', Some(idx)) => board.add_door(pos, index_to_key_color(idx)),
+                ('S', None) => board.add_spring(pos),
+                ('G', None) => board.add_gravity_switch(pos),
+                ('~', None) => board.add_water(pos),
+                ('M', None) => board.add_enemy(pos, EnemyKind::Walker),
+                ('F', None) => board.add_enemy(pos, EnemyKind::Flyer),
+                ('Q', None) => board.add_crate(pos),
+                ('N', None) => board.add_chirp_block(pos, true),
+                ('n', None) => board.add_chirp_block(pos, false),
+                ('_', Some(idx)) => board.add_plate(pos, idx),
                 ('w', Some(idx)) => board.add_respawn(pos, index_to_color(idx)),
                 ('W', Some(idx)) => board.add_warp(pos, index_to_color(idx)),
+                ('Y', Some(idx)) => board.add_portal(pos, index_to_color(idx)),
                 (' ', None) => {},
                 _ => panic!("error reading level {}, position {:?}", level_num, pos),
             }
         }
     }
 
-    board.build()
+    if let Some(pos) = schedules.keys().next() { panic!("error reading lasors for level {}, no lasor at {:?}", level_num, pos); }
+
+    let mut board = board.build();
+    let physics = load_physics(level_num).unwrap_or_else(|err| panic!("error reading physics for level {}, {}", level_num, err));
+    board.set_physics(physics);
+    board
 }
 
 fn digit(c: char) -> Option<u32> {
     if c >= '0' && c <= '9' { Some(c as u32 - '0' as u32) } else { None }
 }
 
-fn index_to_color(index: u32) -> WarpColor {
-    match index {
-        0 => WarpColor::Green,
-        1 => WarpColor::Blue,
-        2 => WarpColor::Pink,
-        _ => panic!("invalid warp index"),
+// one-way platforms may be marked in the index file as made of ice ('i') or mud ('m')
+fn index_to_material(c: char) -> Material {
+    match c {
+        'i' => Material::Ice,
+        'm' => Material::Mud,
+        _ => Material::Normal,
     }
 }
 
+// gravity zones are marked in the index file as low ('l') or reversed ('r') gravity
+fn index_to_gravity_zone(c: char) -> Option<GravityZone> {
+    match c {
+        'l' => Some(GravityZone::Low),
+        'r' => Some(GravityZone::Reversed),
+        _ => None,
+    }
+}
+
+fn schedule(schedules: &mut HashMap<Idx2, LasorSchedule>, pos: Idx2) -> LasorSchedule {
+    schedules.remove(&pos).unwrap_or_else(LasorSchedule::standard)
+}
+
+fn index_to_color(index: u32) -> WarpColor {
+    WarpColor::from_index(index).expect("invalid warp index")
+}
+
+fn index_to_key_color(index: u32) -> KeyColor {
+    KeyColor::from_index(index).expect("invalid key index")
+}
+
 struct LevelFile { dims: Idx2, grid: Vec<Vec<char>> }
 
 impl LevelFile {
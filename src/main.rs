@@ -51,6 +51,13 @@ impl GameApp {
         GameApp { input: GameInput::new(), level: 0, board: level_loader::load(0) }
     }
 
+    fn reload_physics(&mut self) {
+        match level_loader::load_physics(self.level) {
+            Ok(physics) => self.board.set_physics(physics),
+            Err(err) => eprintln!("error reloading physics, {}", err),
+        }
+    }
+
     fn load_next_level(&mut self) {
         self.level = (self.level + 1) % LEVEL_COUNT;
         self.board = level_loader::load(self.level);
@@ -79,7 +86,9 @@ impl App<AssetId> for GameApp {
     }
 
     fn key_down(&mut self, key: KeyCode, _: &mut AppContext<AssetId>) {
-        if let Some(event) = self.input.key_down(key) {
+        if cfg!(debug_assertions) && key == KeyCode::R {
+            self.reload_physics();
+        } else if let Some(event) = self.input.key_down(key) {
             self.board.input(event);
         }
     }